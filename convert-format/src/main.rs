@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::fs;
+use std::path::Path;
+
+use mesh::io::Format;
+use mesh::{gmsh, io, Mesh};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Input mesh in Gmsh MSH format
+    #[arg(long)]
+    input_mesh: String,
+
+    /// Directory where one file per tag and "all" are written
+    #[arg(long)]
+    output_path: String,
+
+    /// Format of the written surfaces, e.g. "gifti" for 1005.surf.gii
+    #[arg(long, default_value = "text")]
+    format: Format,
+}
+
+fn main() {
+    let args = Args::parse();
+    if let Err(error) = run(&args) {
+        eprintln!("error: {:#}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &Args) -> Result<()> {
+    let mesh = gmsh::read_msh(&args.input_mesh)?;
+
+    fs::create_dir_all(&args.output_path)
+        .context(format!("unable to create output path {}", args.output_path))?;
+
+    for (name, surface) in surfaces_by_tag(&mesh) {
+        let surface = surface.remove_unreferenced_vertices();
+        let file_name =
+            Path::new(&args.output_path).join(format!("{}.{}", name, args.format.extension()));
+        let file_name = file_name.to_str().context(format!(
+            "output file name {:?} is not valid UTF-8",
+            file_name
+        ))?;
+        io::write_mesh_as(file_name, &surface, args.format)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the combined "all" surface followed by one surface per tag, in
+/// order of first appearance.
+fn surfaces_by_tag(mesh: &Mesh) -> Vec<(String, Mesh)> {
+    let tags = mesh.tags.clone().unwrap_or_default();
+
+    let mut unique_tags = Vec::new();
+    for tag in &tags {
+        if !unique_tags.contains(tag) {
+            unique_tags.push(*tag);
+        }
+    }
+
+    let mut surfaces = vec![("all".to_string(), mesh.clone())];
+    for tag in unique_tags {
+        let surface = mesh.filter_triangles(|i, _| tags[i] == tag);
+        surfaces.push((tag.to_string(), surface));
+    }
+
+    surfaces
+}
@@ -0,0 +1,186 @@
+use std::str::FromStr;
+
+use mesh::vector::Vector3;
+
+/// Ray directions given on the command line as a comma-separated list:
+/// `x`, `y`, `z` (both signs), `+x`, `-z`, ... (one sign), `a:b:c` (any
+/// vector, gets normalized) or `fibonacci:N` (N directions evenly spread
+/// over the unit sphere).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Directions(pub Vec<Vector3>);
+
+impl FromStr for Directions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut directions = Vec::new();
+
+        for item in s.split(',').map(str::trim) {
+            if let Some(n) = item.strip_prefix("fibonacci:") {
+                let n: usize = n
+                    .parse()
+                    .map_err(|_| format!("invalid number of directions in {}", item))?;
+                directions.extend(fibonacci_sphere(n));
+                continue;
+            }
+
+            let (signs, axis): (&[f64], &str) = match item.chars().next() {
+                Some('+') => (&[1.0], &item[1..]),
+                Some('-') if item.len() == 2 => (&[-1.0], &item[1..]),
+                _ => (&[1.0, -1.0], item),
+            };
+            let unit = match axis {
+                "x" => Some(vector(1.0, 0.0, 0.0)),
+                "y" => Some(vector(0.0, 1.0, 0.0)),
+                "z" => Some(vector(0.0, 0.0, 1.0)),
+                _ => None,
+            };
+            if let Some(unit) = unit {
+                for sign in signs {
+                    directions.push(vector(sign * unit.x, sign * unit.y, sign * unit.z));
+                }
+                continue;
+            }
+
+            let components: Vec<f64> = item
+                .split(':')
+                .map(|c| c.parse::<f64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("could not parse direction {}", item))?;
+            if components.len() != 3 {
+                return Err(format!("could not parse direction {}", item));
+            }
+            let length = components.iter().map(|c| c * c).sum::<f64>().sqrt();
+            if length == 0.0 {
+                return Err(format!("direction {} has zero length", item));
+            }
+            directions.push(vector(
+                components[0] / length,
+                components[1] / length,
+                components[2] / length,
+            ));
+        }
+
+        if directions.is_empty() {
+            return Err("no directions given".to_string());
+        }
+
+        Ok(Directions(directions))
+    }
+}
+
+/// How many of the cast rays need to hit the surface for a point to count
+/// as inside: `all`, `majority` or `at-least:K`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Vote {
+    All,
+    Majority,
+    AtLeast(usize),
+}
+
+impl Vote {
+    pub fn is_inside(&self, num_hits: usize, num_directions: usize) -> bool {
+        match self {
+            Vote::All => num_hits == num_directions,
+            Vote::Majority => 2 * num_hits > num_directions,
+            Vote::AtLeast(k) => num_hits >= *k,
+        }
+    }
+
+    /// Checks that `at-least:K` asks for no more hits than there are rays.
+    pub fn check(&self, num_directions: usize) -> Result<(), String> {
+        match self {
+            Vote::AtLeast(k) if *k > num_directions => Err(format!(
+                "--vote at-least:{} needs {} rays but --directions only gives {}",
+                k, k, num_directions
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Vote {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Vote::All),
+            "majority" => Ok(Vote::Majority),
+            _ => {
+                let k = s
+                    .strip_prefix("at-least:")
+                    .and_then(|k| k.parse().ok())
+                    .filter(|&k| k > 0)
+                    .ok_or(format!(
+                        "expected all, majority or at-least:K with K at least 1 but got {}",
+                        s
+                    ))?;
+                Ok(Vote::AtLeast(k))
+            }
+        }
+    }
+}
+
+fn vector(x: f64, y: f64, z: f64) -> Vector3 {
+    Vector3 { x, y, z }
+}
+
+/// Evenly spread points on the unit sphere along a golden-angle spiral.
+pub fn fibonacci_sphere(n: usize) -> Vec<Vector3> {
+    let golden_angle = std::f64::consts::PI * (3.0 - 5.0_f64.sqrt());
+
+    (0..n)
+        .map(|i| {
+            let z = 1.0 - 2.0 * (i as f64 + 0.5) / n as f64;
+            let radius = (1.0 - z * z).sqrt();
+            let phi = golden_angle * i as f64;
+            vector(radius * phi.cos(), radius * phi.sin(), z)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_directions() {
+        let Directions(directions) = "x,-z,+y".parse().unwrap();
+        assert_eq!(
+            directions,
+            vec![
+                vector(1.0, 0.0, 0.0),
+                vector(-1.0, 0.0, 0.0),
+                vector(0.0, 0.0, -1.0),
+                vector(0.0, 1.0, 0.0),
+            ]
+        );
+
+        let Directions(directions) = "0:3:4".parse().unwrap();
+        assert_eq!(directions, vec![vector(0.0, 0.6, 0.8)]);
+
+        let Directions(directions) = "fibonacci:50".parse().unwrap();
+        assert_eq!(directions.len(), 50);
+        for d in directions {
+            assert!(((d.x * d.x + d.y * d.y + d.z * d.z) - 1.0).abs() < 1.0e-12);
+        }
+
+        assert!("w".parse::<Directions>().is_err());
+        assert!("1:2".parse::<Directions>().is_err());
+        assert!("0:0:0".parse::<Directions>().is_err());
+    }
+
+    #[test]
+    fn test_vote() {
+        assert!(Vote::All.is_inside(4, 4));
+        assert!(!Vote::All.is_inside(3, 4));
+        assert!(!Vote::Majority.is_inside(2, 4));
+        assert!(Vote::Majority.is_inside(3, 4));
+        assert_eq!("at-least:2".parse(), Ok(Vote::AtLeast(2)));
+        assert!(Vote::AtLeast(2).is_inside(2, 6));
+        assert!("some".parse::<Vote>().is_err());
+        assert!("at-least:0".parse::<Vote>().is_err());
+        assert!(Vote::AtLeast(6).check(6).is_ok());
+        assert!(Vote::AtLeast(7).check(6).is_err());
+    }
+}
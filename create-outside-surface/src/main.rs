@@ -1,17 +1,21 @@
-use clap::Parser;
-use std::collections::{HashMap, HashSet};
+use anyhow::{ensure, Result};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use rayon::prelude::*;
+use std::collections::HashSet;
 
-mod intersection;
-mod io;
-mod tiles;
-mod triangle;
-mod vector;
+use mesh::io::Format;
+use mesh::vector::Vector3;
+use mesh::winding::WindingNumber;
+use mesh::{bvh, io, Triplet};
 
-use crate::triangle::Triplet;
-use crate::vector::Vector3;
+mod directions;
+mod tiles;
+mod voxels;
 
-#[macro_use]
-extern crate anyhow;
+use crate::directions::{Directions, Vote};
+use crate::tiles::TileSteps;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -23,23 +27,313 @@ struct Args {
     /// Output file
     #[arg(short, long)]
     output_file: String,
+
+    /// Format of the input file: "text", "msh", "stl", "obj", "ply",
+    /// "gifti", "freesurfer" or "binary". By default it is chosen by file
+    /// extension.
+    #[arg(long)]
+    input_format: Option<Format>,
+
+    /// Format of the output file: "text", "stl", "stl-ascii", "obj", "ply",
+    /// "ply-ascii", "vtk", "vtp", "gifti", "freesurfer", "binary" or
+    /// "binary-f32". By default it is chosen by file extension.
+    #[arg(long)]
+    output_format: Option<Format>,
+
+    /// Triangle indices in the text input start at 1, as in Gmsh, instead
+    /// of 0
+    #[arg(long)]
+    one_based: bool,
+
+    /// Also write the whole input mesh with the classification attached,
+    /// as .vtk or .vtp for ParaView: "inside" per vertex (rays and
+    /// winding classifiers) and "outside" per triangle
+    #[arg(long)]
+    annotated_output: Option<String>,
+
+    /// Number of threads for ray casting (0 uses all available cores)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+
+    /// How to decide whether a vertex is inside
+    #[arg(long, value_enum, default_value_t = Classifier::Rays)]
+    classifier: Classifier,
+
+    /// Vertices with an absolute winding number above this are inside
+    #[arg(long, default_value_t = 0.75)]
+    winding_threshold: f64,
+
+    /// Voxel edge length for the voxels classifier, in mesh units
+    #[arg(long, default_value_t = 1.0, value_parser = positive_number)]
+    voxel_size: f64,
+
+    /// Spatial structure used to find ray-triangle intersections
+    #[arg(long, value_enum, default_value_t = Accelerator::Bvh)]
+    accelerator: Accelerator,
+
+    /// Number of tiles along each axis for the tiles accelerator, or "auto"
+    /// to size tiles from the mean edge length and triangle count
+    #[arg(long, default_value = "100")]
+    tile_steps: TileSteps,
+
+    /// Print how many points and triangles the tiles hold
+    #[arg(long)]
+    tile_stats: bool,
+
+    /// Ray directions, e.g. "x,y,z", "+x,-z", "0.6:0:0.8" or "fibonacci:20"
+    #[arg(long, default_value = "x,y")]
+    directions: Directions,
+
+    /// How many rays need to hit the surface for a vertex to be inside:
+    /// "all", "majority" or "at-least:K"
+    #[arg(long, default_value = "all")]
+    vote: Vote,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Classifier {
+    /// Cast rays from each vertex and count the hits
+    Rays,
+    /// Generalized winding number of each vertex with respect to the mesh
+    Winding,
+    /// Keep triangles next to voxels reached by a flood fill from outside
+    Voxels,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Accelerator {
+    /// Bounding volume hierarchy, works for any ray direction
+    Bvh,
+    /// Fixed grid of tiles along the x and y axes
+    Tiles,
+}
+
+fn positive_number(s: &str) -> Result<f64, String> {
+    match s.parse() {
+        Ok(x) if x > 0.0 && f64::is_finite(x) => Ok(x),
+        _ => Err(format!("expected a positive number but got {}", s)),
+    }
 }
 
 fn main() {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+
+    if let Err(error) = check_options(&matches, &args) {
+        error.exit();
+    }
+
+    if let Err(error) = run(&args) {
+        eprintln!("error: {:#}", error);
+        std::process::exit(1);
+    }
+}
+
+/// Rejects options given on the command line which the chosen classifier or
+/// accelerator does not use, and votes which cannot be reached.
+fn check_options(matches: &ArgMatches, args: &Args) -> Result<(), clap::Error> {
+    let rays = args.classifier == Classifier::Rays;
+    let tiles = rays && args.accelerator == Accelerator::Tiles;
+    let options = [
+        ("accelerator", rays, "--classifier rays"),
+        (
+            "directions",
+            rays && !tiles,
+            "--classifier rays with --accelerator bvh",
+        ),
+        (
+            "vote",
+            rays && !tiles,
+            "--classifier rays with --accelerator bvh",
+        ),
+        ("tile_steps", tiles, "--accelerator tiles"),
+        ("tile_stats", tiles, "--accelerator tiles"),
+        (
+            "winding_threshold",
+            args.classifier == Classifier::Winding,
+            "--classifier winding",
+        ),
+        (
+            "voxel_size",
+            args.classifier == Classifier::Voxels,
+            "--classifier voxels",
+        ),
+    ];
+    for (id, applies, requirement) in options {
+        if !applies && matches.value_source(id) == Some(ValueSource::CommandLine) {
+            return Err(Args::command().error(
+                ErrorKind::ArgumentConflict,
+                format!("--{} only applies to {}", id.replace('_', "-"), requirement),
+            ));
+        }
+    }
+
+    args.vote
+        .check(args.directions.0.len())
+        .map_err(|message| Args::command().error(ErrorKind::ValueValidation, message))
+}
 
-    let (coordinates, triangles) = io::read_mesh(&args.input_file).unwrap();
+fn run(args: &Args) -> Result<()> {
+    if let Some(file_name) = &args.annotated_output {
+        ensure!(
+            Format::from_file_name(file_name).writes_scalars(),
+            "annotated output {} would lose its scalars, use .vtk or .vtp",
+            file_name
+        );
+    }
+
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build_global()?;
+
+    let input_format = args
+        .input_format
+        .unwrap_or_else(|| Format::from_file_name(&args.input_file));
+    let output_format = args
+        .output_format
+        .unwrap_or_else(|| Format::from_file_name(&args.output_file));
+
+    let mesh = io::read_mesh_indexed_from(&args.input_file, input_format, args.one_based as usize)?
+        .remove_duplicate_triangles();
+    let coordinates = &mesh.vertices;
+
+    let (outside, inside_points) = if args.classifier == Classifier::Voxels {
+        let outer = voxels::find_outer_triangles(coordinates, &mesh.triangles, args.voxel_size)?;
+        (outer, None)
+    } else {
+        let inside_points = match (args.classifier, args.accelerator) {
+            (Classifier::Rays, Accelerator::Bvh) => find_inside_points_with_bvh(
+                coordinates,
+                &mesh.triangles,
+                &args.directions.0,
+                args.vote,
+            ),
+            (Classifier::Rays, Accelerator::Tiles) => find_inside_points_with_tiles(
+                coordinates,
+                &mesh.triangles,
+                args.tile_steps,
+                args.tile_stats,
+            ),
+            _ => find_inside_points_with_winding_number(
+                coordinates,
+                &mesh.triangles,
+                args.winding_threshold,
+            ),
+        };
+
+        let outside = mesh
+            .triangles
+            .iter()
+            .map(|(a, b, c)| {
+                !inside_points.contains(a)
+                    && !inside_points.contains(b)
+                    && !inside_points.contains(c)
+            })
+            .collect();
+        (outside, Some(inside_points))
+    };
+
+    if let Some(file_name) = &args.annotated_output {
+        let mut annotated = mesh.clone();
+        if let Some(inside_points) = &inside_points {
+            let inside = (0..coordinates.len())
+                .map(|i| inside_points.contains(&i) as u8 as f64)
+                .collect();
+            annotated
+                .vertex_scalars
+                .push(("inside".to_string(), inside));
+        }
+        let outside_values = outside.iter().map(|o| *o as u8 as f64).collect();
+        annotated
+            .triangle_scalars
+            .push(("outside".to_string(), outside_values));
+        io::write_mesh(file_name, &annotated)?;
+    }
+
+    let outside_surface = mesh.filter_triangles(|i, _| outside[i]);
+
+    io::write_mesh_as(
+        &args.output_file,
+        &outside_surface.remove_unreferenced_vertices(),
+        output_format,
+    )
+}
+
+/// Points for which enough rays, according to the vote, hit the surface.
+fn find_inside_points_with_bvh(
+    coordinates: &[Vector3],
+    triangles: &[Triplet],
+    directions: &[Vector3],
+    vote: Vote,
+) -> HashSet<usize> {
+    let bvh = bvh::Bvh::new(coordinates, triangles);
+
+    (0..coordinates.len())
+        .into_par_iter()
+        .filter(|i| {
+            let num_hits = directions
+                .iter()
+                .filter(|direction| bvh.any_hit(&coordinates[*i], direction))
+                .count();
+            vote.is_inside(num_hits, directions.len())
+        })
+        .collect()
+}
 
-    let num_steps = 100;
-    let step = tiles::get_step_sizes(num_steps, &coordinates);
+/// Vertices whose winding number, leaving out their own triangles, exceeds
+/// the threshold in absolute value. Vertices on the outer shell have a
+/// winding number close to 0.5, vertices enclosed by it close to 1 or more.
+fn find_inside_points_with_winding_number(
+    coordinates: &[Vector3],
+    triangles: &[Triplet],
+    threshold: f64,
+) -> HashSet<usize> {
+    let winding_number = WindingNumber::new(coordinates, triangles);
+
+    (0..coordinates.len())
+        .into_par_iter()
+        .filter(|i| winding_number.evaluate(&coordinates[*i], Some(*i)).abs() > threshold)
+        .collect()
+}
+
+fn find_inside_points_with_tiles(
+    coordinates: &[Vector3],
+    triangles: &[Triplet],
+    tile_steps: TileSteps,
+    print_stats: bool,
+) -> HashSet<usize> {
+    let step = match tile_steps {
+        TileSteps::Fixed(num_steps) => tiles::get_step_sizes(num_steps, coordinates),
+        TileSteps::Auto => tiles::get_auto_step_sizes(coordinates, triangles),
+    };
 
     let (tiles_to_points_along_x, tiles_to_points_along_y) =
-        tiles::distribute_points_to_tiles(&coordinates, step);
+        tiles::distribute_points_to_tiles(coordinates, step);
     let (tiles_to_triangles_along_x, tiles_to_triangles_along_y) =
-        tiles::distribute_triangles_to_tiles(&coordinates, &triangles, step);
+        tiles::distribute_triangles_to_tiles(coordinates, triangles, step);
+
+    if print_stats {
+        println!("tile size: {} {} {}", step.x, step.y, step.z);
+        println!(
+            "points along x: {}",
+            tiles::occupancy(&tiles_to_points_along_x)
+        );
+        println!(
+            "points along y: {}",
+            tiles::occupancy(&tiles_to_points_along_y)
+        );
+        println!(
+            "triangles along x: {}",
+            tiles::occupancy(&tiles_to_triangles_along_x)
+        );
+        println!(
+            "triangles along y: {}",
+            tiles::occupancy(&tiles_to_triangles_along_y)
+        );
+    }
 
     let inside_points_along_x = tiles::find_inside_points(
-        &coordinates,
+        coordinates,
         &tiles_to_points_along_x,
         &tiles_to_triangles_along_x,
         &Vector3 {
@@ -54,7 +348,7 @@ fn main() {
         },
     );
     let inside_points_along_y = tiles::find_inside_points(
-        &coordinates,
+        coordinates,
         &tiles_to_points_along_y,
         &tiles_to_triangles_along_y,
         &Vector3 {
@@ -69,47 +363,106 @@ fn main() {
         },
     );
 
-    let inside_points: HashSet<_> = inside_points_along_x
+    inside_points_along_x
         .intersection(&inside_points_along_y)
-        .collect();
+        .copied()
+        .collect()
+}
 
-    let mut outside_triangles = HashSet::new();
-    for (a, b, c) in &triangles {
-        if !inside_points.contains(a) && !inside_points.contains(b) && !inside_points.contains(c) {
-            outside_triangles.insert((*a, *b, *c));
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `check_options` accepts the options, after the input and
+    /// output files.
+    fn accepts(options: &[&str]) -> bool {
+        let command_line = ["ray", "-i", "in.txt", "-o", "out.txt"];
+        let matches = Args::command()
+            .try_get_matches_from(command_line.iter().chain(options))
+            .unwrap();
+        let args = Args::from_arg_matches(&matches).unwrap();
+        check_options(&matches, &args).is_ok()
     }
 
-    let (coordinates, triangles) = remove_unused_points(&coordinates, &outside_triangles);
-    io::write_mesh(&args.output_file, &coordinates, &triangles);
-}
+    #[test]
+    fn test_check_options() {
+        assert!(accepts(&[]));
+        assert!(accepts(&["--directions", "y,x", "--vote", "at-least:1"]));
+        assert!(accepts(&["--accelerator", "tiles", "--tile-steps", "auto"]));
 
-fn remove_unused_points(
-    coordinates: &[Vector3],
-    triangles: &HashSet<Triplet>,
-) -> (Vec<Vector3>, HashSet<Triplet>) {
-    let used_indices: HashSet<usize> = triangles
-        .iter()
-        .flat_map(|&(a, b, c)| vec![a, b, c])
-        .collect();
-
-    let mut used_indices: Vec<usize> = used_indices.into_iter().collect();
-    used_indices.sort_unstable();
-
-    let mut new_points = Vec::new();
-    let mut point_index_map: HashMap<usize, usize> = HashMap::new();
-    for (i, j) in used_indices.iter().enumerate() {
-        point_index_map.insert(*j, i);
-        new_points.push(coordinates[*j]);
-    }
+        // given options are rejected whatever their value
+        assert!(!accepts(&[
+            "--classifier",
+            "winding",
+            "--directions",
+            "x,y"
+        ]));
+        assert!(!accepts(&[
+            "--classifier",
+            "voxels",
+            "--accelerator",
+            "bvh"
+        ]));
+        assert!(!accepts(&["--accelerator", "tiles", "--vote", "all"]));
+        assert!(!accepts(&["--tile-steps", "100"]));
+        assert!(!accepts(&["--tile-stats"]));
+        assert!(!accepts(&["--vote", "at-least:5"]));
 
-    let mut new_triangles: HashSet<Triplet> = HashSet::new();
-    for (a, b, c) in triangles {
-        let a_new = *point_index_map.get(a).unwrap();
-        let b_new = *point_index_map.get(b).unwrap();
-        let c_new = *point_index_map.get(c).unwrap();
-        new_triangles.insert((a_new, b_new, c_new));
+        assert!(accepts(&[
+            "--classifier",
+            "winding",
+            "--winding-threshold",
+            "0.5"
+        ]));
+        assert!(!accepts(&["--winding-threshold", "0.75"]));
+        assert!(accepts(&["--classifier", "voxels", "--voxel-size", "2"]));
+        assert!(!accepts(&["--classifier", "winding", "--voxel-size", "1"]));
     }
 
-    (new_points, new_triangles)
+    #[test]
+    fn test_unreferenced_vertex() {
+        // a box around a tetrahedron, and a vertex no triangle refers to
+        let mut coordinates: Vec<Vector3> = (0..8)
+            .map(|i| Vector3 {
+                x: if i & 1 == 0 { -5.0 } else { 5.0 },
+                y: if i & 2 == 0 { -5.0 } else { 5.0 },
+                z: if i & 4 == 0 { -5.0 } else { 5.0 },
+            })
+            .collect();
+        let mut triangles = Vec::new();
+        for (a, b, c, d) in [
+            (0, 1, 3, 2),
+            (4, 6, 7, 5),
+            (0, 4, 5, 1),
+            (2, 3, 7, 6),
+            (0, 2, 6, 4),
+            (1, 5, 7, 3),
+        ] {
+            triangles.push((a, b, c));
+            triangles.push((a, c, d));
+        }
+        coordinates.extend(
+            [
+                (0.1, 0.2, 0.0),
+                (1.1, 0.2, 0.0),
+                (0.1, 1.2, 0.0),
+                (0.1, 0.2, 1.0),
+            ]
+            .map(|(x, y, z)| Vector3 { x, y, z }),
+        );
+        triangles.extend([(8, 10, 9), (8, 9, 11), (9, 10, 11), (8, 11, 10)]);
+        coordinates.push(Vector3 {
+            x: 50.0,
+            y: 50.0,
+            z: 50.0,
+        });
+
+        let directions: Directions = "x,y".parse().unwrap();
+        let with_bvh =
+            find_inside_points_with_bvh(&coordinates, &triangles, &directions.0, Vote::All);
+        let with_tiles =
+            find_inside_points_with_tiles(&coordinates, &triangles, TileSteps::Fixed(10), false);
+        assert_eq!(with_tiles, with_bvh);
+        assert_eq!(with_tiles, HashSet::from([8, 9, 10, 11]));
+    }
 }
@@ -1,12 +1,16 @@
 use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
 
-use crate::intersection;
-use crate::triangle::Triplet;
-use crate::vector::Vector3;
+use mesh::intersection;
+use mesh::vector::{vec_dot_vec, vec_minus_vec, Vector3};
+use mesh::Triplet;
+use rayon::prelude::*;
 
-type TileIndexMap = HashMap<(isize, isize), HashSet<usize>>;
-type TileTripletMap = HashMap<(isize, isize), HashSet<Triplet>>;
+type TileIndexMap = HashMap<(isize, isize), Vec<usize>>;
+type TileTripletMap = HashMap<(isize, isize), Vec<Triplet>>;
 
+/// Tiles are processed in parallel on the current rayon thread pool.
 pub fn find_inside_points(
     coordinates: &[Vector3],
     tiles_to_points: &TileIndexMap,
@@ -14,32 +18,105 @@ pub fn find_inside_points(
     ray_direction: &Vector3,
     ray_direction_opposite: &Vector3,
 ) -> HashSet<usize> {
-    let mut inside_points = HashSet::new();
-
-    for &(ix, iz) in tiles_to_points.keys() {
-        let triangles = &tiles_to_triangles[&(ix, iz)];
-        for point_index in &tiles_to_points[&(ix, iz)] {
-            let x = coordinates[*point_index].x;
-            let y = coordinates[*point_index].y;
-            let z = coordinates[*point_index].z;
-
-            if intersection::ray_intersects_batch(
-                &Vector3 { x, y, z },
-                ray_direction,
-                coordinates,
-                triangles,
-            ) && intersection::ray_intersects_batch(
-                &Vector3 { x, y, z },
-                ray_direction_opposite,
-                coordinates,
-                triangles,
-            ) {
-                inside_points.insert(*point_index);
-            }
+    tiles_to_points
+        .par_iter()
+        .flat_map_iter(|(tile, point_indices)| {
+            // points without triangles in their tile, e.g. unreferenced
+            // vertices, are hit by nothing
+            let triangles = tiles_to_triangles.get(tile).map_or(&[][..], Vec::as_slice);
+            point_indices.iter().copied().filter(move |point_index| {
+                let point = &coordinates[*point_index];
+
+                intersection::ray_intersects_batch(point, ray_direction, coordinates, triangles)
+                    && intersection::ray_intersects_batch(
+                        point,
+                        ray_direction_opposite,
+                        coordinates,
+                        triangles,
+                    )
+            })
+        })
+        .collect()
+}
+
+/// Number of tiles along each axis of the bounding box, or `auto` to derive
+/// the tile size from the mesh.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TileSteps {
+    Fixed(usize),
+    Auto,
+}
+
+impl FromStr for TileSteps {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "auto" {
+            return Ok(TileSteps::Auto);
+        }
+        match s.parse() {
+            Ok(n) if n > 0 => Ok(TileSteps::Fixed(n)),
+            _ => Err(format!("expected a positive number or auto but got {}", s)),
+        }
+    }
+}
+
+/// Tile statistics, either for points or for triangles per tile.
+pub struct Occupancy {
+    pub num_tiles: usize,
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+}
+
+impl fmt::Display for Occupancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} tiles, min {}, max {}, mean {:.1}",
+            self.num_tiles, self.min, self.max, self.mean
+        )
+    }
+}
+
+pub fn occupancy<T>(tiles: &HashMap<(isize, isize), Vec<T>>) -> Occupancy {
+    let counts: Vec<usize> = tiles.values().map(|v| v.len()).collect();
+    let total: usize = counts.iter().sum();
+
+    Occupancy {
+        num_tiles: counts.len(),
+        min: counts.iter().copied().min().unwrap_or(0),
+        max: counts.iter().copied().max().unwrap_or(0),
+        mean: total as f64 / counts.len().max(1) as f64,
+    }
+}
+
+/// Square tiles of twice the mean edge length, but never more tiles per
+/// plane than there are triangles.
+pub fn get_auto_step_sizes(coordinates: &[Vector3], triangles: &[Triplet]) -> Vector3 {
+    let default_num_steps = 100;
+
+    let mut edge_length_sum = 0.0;
+    for (a, b, c) in triangles {
+        for (i, j) in [(a, b), (b, c), (c, a)] {
+            let d = vec_minus_vec(&coordinates[*i], &coordinates[*j]);
+            edge_length_sum += vec_dot_vec(&d, &d).sqrt();
         }
     }
+    if edge_length_sum == 0.0 {
+        return get_step_sizes(default_num_steps, coordinates);
+    }
+    let mean_edge_length = edge_length_sum / (3 * triangles.len()) as f64;
+
+    let extent = get_step_sizes(1, coordinates);
+    let largest_extent = extent.x.max(extent.y).max(extent.z);
+    let step = (2.0 * mean_edge_length).max(largest_extent / (triangles.len() as f64).sqrt());
 
-    inside_points
+    Vector3 {
+        x: step,
+        y: step,
+        z: step,
+    }
 }
 
 pub fn get_step_sizes(num_steps: usize, coordinates: &[Vector3]) -> Vector3 {
@@ -84,14 +161,8 @@ pub fn distribute_points_to_tiles(
         let iy = tile_index(point.y, 0.0, step.y);
         let iz = tile_index(point.z, 0.0, step.z);
 
-        map_along_x
-            .entry((iy, iz))
-            .or_insert(HashSet::new())
-            .insert(i);
-        map_along_y
-            .entry((ix, iz))
-            .or_insert(HashSet::new())
-            .insert(i);
+        map_along_x.entry((iy, iz)).or_insert(Vec::new()).push(i);
+        map_along_y.entry((ix, iz)).or_insert(Vec::new()).push(i);
     }
 
     (map_along_x, map_along_y)
@@ -99,7 +170,7 @@ pub fn distribute_points_to_tiles(
 
 pub fn distribute_triangles_to_tiles(
     coordinates: &[Vector3],
-    triangles: &HashSet<Triplet>,
+    triangles: &[Triplet],
     step: Vector3,
 ) -> (TileTripletMap, TileTripletMap) {
     let mut map_along_x = HashMap::new();
@@ -134,8 +205,8 @@ pub fn distribute_triangles_to_tiles(
             for iz in iz_min..=iz_max {
                 map_along_x
                     .entry((iy, iz))
-                    .or_insert(HashSet::new())
-                    .insert((*a, *b, *c));
+                    .or_insert(Vec::new())
+                    .push((*a, *b, *c));
             }
         }
 
@@ -143,8 +214,8 @@ pub fn distribute_triangles_to_tiles(
             for iz in iz_min..=iz_max {
                 map_along_y
                     .entry((ix, iz))
-                    .or_insert(HashSet::new())
-                    .insert((*a, *b, *c));
+                    .or_insert(Vec::new())
+                    .push((*a, *b, *c));
             }
         }
     }
@@ -170,4 +241,134 @@ mod tests {
         assert_eq!(tile_index(-0.4, 0.0, 0.4), -1);
         assert_eq!(tile_index(-0.4000001, 0.0, 0.4), -2);
     }
+
+    #[test]
+    fn test_parallel_matches_single_thread() {
+        // a closed box with a grid of small triangles inside and outside of
+        // it, since every point needs to be in a tile with triangles
+        let mut coordinates = Vec::new();
+        for i in 0..8 {
+            coordinates.push(Vector3 {
+                x: if i & 1 == 0 { 0.0 } else { 10.0 },
+                y: if i & 2 == 0 { 0.0 } else { 10.0 },
+                z: if i & 4 == 0 { 0.0 } else { 10.0 },
+            });
+        }
+        let mut triangles = Vec::new();
+        for (a, b, c, d) in [
+            (0, 1, 3, 2),
+            (4, 6, 7, 5),
+            (0, 4, 5, 1),
+            (2, 3, 7, 6),
+            (0, 2, 6, 4),
+            (1, 5, 7, 3),
+        ] {
+            triangles.push((a, b, c));
+            triangles.push((a, c, d));
+        }
+        for i in 0..1000 {
+            let (x, y, z) = (
+                -1.3 + 1.3 * (i % 10) as f64,
+                -1.1 + 1.3 * (i / 10 % 10) as f64,
+                0.7 + 1.1 * (i / 100) as f64,
+            );
+            let n = coordinates.len();
+            coordinates.push(Vector3 { x, y, z });
+            coordinates.push(Vector3 { x: x + 0.01, y, z });
+            coordinates.push(Vector3 { x, y: y + 0.01, z });
+            triangles.push((n, n + 1, n + 2));
+        }
+
+        let step = get_step_sizes(7, &coordinates);
+        let (points, _) = distribute_points_to_tiles(&coordinates, step);
+        let (tiles, _) = distribute_triangles_to_tiles(&coordinates, &triangles, step);
+        let inside = |num_threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                find_inside_points(
+                    &coordinates,
+                    &points,
+                    &tiles,
+                    &Vector3 {
+                        x: 1.0,
+                        y: 0.0,
+                        z: 0.0,
+                    },
+                    &Vector3 {
+                        x: -1.0,
+                        y: 0.0,
+                        z: 0.0,
+                    },
+                )
+            })
+        };
+
+        let single = inside(1);
+        assert!(!single.is_empty() && single.len() < coordinates.len());
+        assert_eq!(inside(4), single);
+    }
+
+    #[test]
+    fn test_isolated_vertex() {
+        // a tetrahedron and a vertex far away which no triangle refers to
+        let coordinates = [
+            (0.0, 0.0, 0.0),
+            (1.0, 0.0, 0.0),
+            (0.0, 1.0, 0.0),
+            (0.0, 0.0, 1.0),
+            (20.0, 20.0, 20.0),
+        ]
+        .map(|(x, y, z)| Vector3 { x, y, z });
+        let triangles = [(0, 2, 1), (0, 1, 3), (1, 2, 3), (0, 3, 2)];
+
+        let step = get_step_sizes(10, &coordinates);
+        let (points, _) = distribute_points_to_tiles(&coordinates, step);
+        let (tiles, _) = distribute_triangles_to_tiles(&coordinates, &triangles, step);
+        let inside = find_inside_points(
+            &coordinates,
+            &points,
+            &tiles,
+            &Vector3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            &Vector3 {
+                x: -1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+        );
+        assert!(!inside.contains(&4));
+    }
+
+    #[test]
+    fn test_tile_steps() {
+        assert_eq!("auto".parse(), Ok(TileSteps::Auto));
+        assert_eq!("50".parse(), Ok(TileSteps::Fixed(50)));
+        assert!("0".parse::<TileSteps>().is_err());
+        assert!("many".parse::<TileSteps>().is_err());
+    }
+
+    #[test]
+    fn test_auto_step_sizes() {
+        // right triangle with legs of length 1 in a 10 x 10 x 10 box
+        let coordinates = [
+            (0.0, 0.0, 0.0),
+            (1.0, 0.0, 0.0),
+            (0.0, 1.0, 0.0),
+            (10.0, 10.0, 10.0),
+        ]
+        .map(|(x, y, z)| Vector3 { x, y, z });
+        let step = get_auto_step_sizes(&coordinates, &[(0, 1, 2)]);
+        assert_eq!(step.x, 10.0);
+
+        let triangles = vec![(0, 1, 2); 10000];
+        let step = get_auto_step_sizes(&coordinates, &triangles);
+        let mean_edge_length = (2.0 + 2.0_f64.sqrt()) / 3.0;
+        assert!((step.x - 2.0 * mean_edge_length).abs() < 1.0e-12);
+    }
 }
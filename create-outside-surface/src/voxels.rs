@@ -0,0 +1,312 @@
+use anyhow::{ensure, Result};
+use rayon::prelude::*;
+use std::collections::VecDeque;
+
+use mesh::triangle::Triangle;
+use mesh::vector::{vec_cross_vec, vec_dot_vec, vec_minus_vec, Vector3};
+use mesh::Triplet;
+
+/// Largest number of voxels in a grid, about 500 MB for the occupied and
+/// exterior flags.
+const MAX_VOXELS: f64 = 2.5e8;
+
+/// Regular grid of cubic voxels covering the mesh with one voxel of padding
+/// on each side, so that the corner voxel is always outside.
+struct VoxelGrid {
+    origin: Vector3,
+    size: f64,
+    dims: [usize; 3],
+}
+
+impl VoxelGrid {
+    fn new(coordinates: &[Vector3], size: f64) -> Result<Self> {
+        let min = [0, 1, 2].map(|axis| {
+            coordinates
+                .iter()
+                .map(|p| component(p, axis))
+                .fold(f64::MAX, f64::min)
+        });
+        let max = [0, 1, 2].map(|axis| {
+            coordinates
+                .iter()
+                .map(|p| component(p, axis))
+                .fold(-f64::MAX, f64::max)
+        });
+
+        let dims = [0, 1, 2].map(|axis| ((max[axis] - min[axis]) / size).floor().max(0.0) + 3.0);
+        let len = dims[0] * dims[1] * dims[2];
+        ensure!(
+            len <= MAX_VOXELS,
+            "a voxel size of {} needs {:.0} x {:.0} x {:.0} voxels, more than {:.0}, use a larger \
+             --voxel-size",
+            size,
+            dims[0],
+            dims[1],
+            dims[2],
+            MAX_VOXELS
+        );
+
+        Ok(VoxelGrid {
+            origin: Vector3 {
+                x: min[0] - size,
+                y: min[1] - size,
+                z: min[2] - size,
+            },
+            size,
+            dims: dims.map(|d| d as usize),
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.dims[0] * self.dims[1] * self.dims[2]
+    }
+
+    fn index(&self, i: usize, j: usize, k: usize) -> usize {
+        (i * self.dims[1] + j) * self.dims[2] + k
+    }
+
+    fn cell(&self, value: f64, origin: f64, axis: usize) -> usize {
+        (((value - origin) / self.size).floor().max(0.0) as usize).min(self.dims[axis] - 1)
+    }
+
+    /// Voxels which the triangle overlaps.
+    fn voxels_of_triangle(&self, triangle: &Triangle) -> Vec<(usize, usize, usize)> {
+        let vertices = [triangle.vertex1, triangle.vertex2, triangle.vertex3];
+        let [(i_min, i_max), (j_min, j_max), (k_min, k_max)] = [0, 1, 2].map(|axis| {
+            let values = vertices.map(|v| component(&v, axis));
+            let origin = component(&self.origin, axis);
+            (
+                self.cell(values.into_iter().fold(f64::MAX, f64::min), origin, axis),
+                self.cell(values.into_iter().fold(-f64::MAX, f64::max), origin, axis),
+            )
+        });
+
+        let half = 0.5 * self.size;
+        let mut voxels = Vec::new();
+        for i in i_min..=i_max {
+            for j in j_min..=j_max {
+                for k in k_min..=k_max {
+                    let center = Vector3 {
+                        x: self.origin.x + (i as f64 + 0.5) * self.size,
+                        y: self.origin.y + (j as f64 + 0.5) * self.size,
+                        z: self.origin.z + (k as f64 + 0.5) * self.size,
+                    };
+                    if triangle_box_overlap(&center, half, triangle) {
+                        voxels.push((i, j, k));
+                    }
+                }
+            }
+        }
+
+        voxels
+    }
+
+    fn neighbors(&self, i: usize, j: usize, k: usize) -> Vec<(usize, usize, usize)> {
+        let mut neighbors = Vec::with_capacity(6);
+        if i > 0 {
+            neighbors.push((i - 1, j, k));
+        }
+        if i + 1 < self.dims[0] {
+            neighbors.push((i + 1, j, k));
+        }
+        if j > 0 {
+            neighbors.push((i, j - 1, k));
+        }
+        if j + 1 < self.dims[1] {
+            neighbors.push((i, j + 1, k));
+        }
+        if k > 0 {
+            neighbors.push((i, j, k - 1));
+        }
+        if k + 1 < self.dims[2] {
+            neighbors.push((i, j, k + 1));
+        }
+        neighbors
+    }
+}
+
+fn component(point: &Vector3, axis: usize) -> f64 {
+    match axis {
+        0 => point.x,
+        1 => point.y,
+        _ => point.z,
+    }
+}
+
+/// Rasterizes the triangles into voxels of the given edge length, flood
+/// fills the empty voxels starting from a corner of the bounding box, and
+/// returns for each triangle whether it touches a voxel reached by the fill.
+pub fn find_outer_triangles(
+    coordinates: &[Vector3],
+    triangles: &[Triplet],
+    voxel_size: f64,
+) -> Result<Vec<bool>> {
+    let grid = VoxelGrid::new(coordinates, voxel_size)?;
+
+    let triangle_voxels: Vec<Vec<(usize, usize, usize)>> = triangles
+        .par_iter()
+        .map(|t| grid.voxels_of_triangle(&Triangle::from_indices(coordinates, *t)))
+        .collect();
+
+    let mut occupied = vec![false; grid.len()];
+    for (i, j, k) in triangle_voxels.iter().flatten() {
+        occupied[grid.index(*i, *j, *k)] = true;
+    }
+
+    let mut exterior = vec![false; grid.len()];
+    let mut queue = VecDeque::new();
+    exterior[0] = true;
+    queue.push_back((0, 0, 0));
+    while let Some((i, j, k)) = queue.pop_front() {
+        for (a, b, c) in grid.neighbors(i, j, k) {
+            let n = grid.index(a, b, c);
+            if !occupied[n] && !exterior[n] {
+                exterior[n] = true;
+                queue.push_back((a, b, c));
+            }
+        }
+    }
+
+    Ok(triangle_voxels
+        .par_iter()
+        .map(|voxels| {
+            voxels.iter().any(|(i, j, k)| {
+                grid.neighbors(*i, *j, *k)
+                    .iter()
+                    .any(|(a, b, c)| exterior[grid.index(*a, *b, *c)])
+            })
+        })
+        .collect())
+}
+
+/// Separating axis test of Akenine-Möller for a triangle and an axis-aligned
+/// cube given by its center and half edge length.
+fn triangle_box_overlap(center: &Vector3, half: f64, triangle: &Triangle) -> bool {
+    let v = [
+        vec_minus_vec(&triangle.vertex1, center),
+        vec_minus_vec(&triangle.vertex2, center),
+        vec_minus_vec(&triangle.vertex3, center),
+    ];
+    let edges = [
+        vec_minus_vec(&v[1], &v[0]),
+        vec_minus_vec(&v[2], &v[1]),
+        vec_minus_vec(&v[0], &v[2]),
+    ];
+    let box_axes = [
+        Vector3 {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        Vector3 {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        },
+        Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        },
+    ];
+
+    let separated = |axis: &Vector3| {
+        let p = v.map(|vertex| vec_dot_vec(&vertex, axis));
+        let r = half * (axis.x.abs() + axis.y.abs() + axis.z.abs());
+        let min = p[0].min(p[1]).min(p[2]);
+        let max = p[0].max(p[1]).max(p[2]);
+        min > r || max < -r
+    };
+
+    // nine cross products of edges and box axes
+    for edge in &edges {
+        for box_axis in &box_axes {
+            if separated(&vec_cross_vec(edge, box_axis)) {
+                return false;
+            }
+        }
+    }
+
+    // box faces, then the triangle plane
+    if box_axes.iter().any(separated) {
+        return false;
+    }
+    !separated(&vec_cross_vec(&edges[0], &edges[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Closed axis-aligned box with 12 triangles.
+    fn cube(min: f64, max: f64, coordinates: &mut Vec<Vector3>, triangles: &mut Vec<Triplet>) {
+        let o = coordinates.len();
+        for i in 0..8 {
+            coordinates.push(Vector3 {
+                x: if i & 1 == 0 { min } else { max },
+                y: if i & 2 == 0 { min } else { max },
+                z: if i & 4 == 0 { min } else { max },
+            });
+        }
+        for (a, b, c, d) in [
+            (0, 1, 3, 2),
+            (4, 6, 7, 5),
+            (0, 4, 5, 1),
+            (2, 3, 7, 6),
+            (0, 2, 6, 4),
+            (1, 5, 7, 3),
+        ] {
+            triangles.push((o + a, o + b, o + c));
+            triangles.push((o + a, o + c, o + d));
+        }
+    }
+
+    #[test]
+    fn test_inner_box_is_removed() {
+        let mut coordinates = Vec::new();
+        let mut triangles = Vec::new();
+        cube(0.0, 10.0, &mut coordinates, &mut triangles);
+        cube(3.0, 7.0, &mut coordinates, &mut triangles);
+
+        let outer = find_outer_triangles(&coordinates, &triangles, 0.5).unwrap();
+        assert_eq!(outer[..12], [true; 12]);
+        assert_eq!(outer[12..], [false; 12]);
+    }
+
+    #[test]
+    fn test_too_many_voxels() {
+        let mut coordinates = Vec::new();
+        let mut triangles = Vec::new();
+        cube(0.0, 10.0, &mut coordinates, &mut triangles);
+
+        let error = find_outer_triangles(&coordinates, &triangles, 1e-9).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("a voxel size of 0.000000001 needs"));
+    }
+
+    #[test]
+    fn test_triangle_box_overlap() {
+        let triangle = Triangle {
+            vertex1: Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            vertex2: Vector3 {
+                x: 2.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            vertex3: Vector3 {
+                x: 0.0,
+                y: 2.0,
+                z: 0.0,
+            },
+        };
+        let at = |x, y, z| Vector3 { x, y, z };
+        assert!(triangle_box_overlap(&at(0.5, 0.5, 0.4), 0.5, &triangle));
+        assert!(!triangle_box_overlap(&at(0.5, 0.5, 0.6), 0.5, &triangle));
+        assert!(!triangle_box_overlap(&at(1.9, 1.9, 0.0), 0.5, &triangle));
+    }
+}
@@ -0,0 +1,215 @@
+//! Compact little endian binary mesh format, read through a memory map.
+//!
+//! Layout, version 1:
+//!
+//! | bytes | content                                          |
+//! |-------|--------------------------------------------------|
+//! | 8     | magic `MESHBIN\0`                                |
+//! | 4     | version (u32)                                    |
+//! | 4     | flags (u32): 1 = f32 vertices, 2 = tags present  |
+//! | 8     | number of vertices (u64)                         |
+//! | 8     | number of triangles (u64)                        |
+//! | ...   | vertex coordinates, x y z each (f64 or f32)      |
+//! | ...   | triangle indices, 3 per triangle (u32)           |
+//! | ...   | one tag per triangle (i32), if present           |
+//!
+//! With f64 vertices the conversion to and from the text format is lossless.
+
+use anyhow::{Context, Result};
+use memmap2::Mmap;
+use std::fs;
+use std::io::{BufWriter, Write};
+
+use crate::mesh::Mesh;
+use crate::vector::Vector3;
+
+const MAGIC: &[u8; 8] = b"MESHBIN\0";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 32;
+
+const FLAG_F32: u32 = 1;
+const FLAG_TAGS: u32 = 2;
+
+pub fn read_binary(file_name: &str) -> Result<Mesh> {
+    let error_message = format!("something went wrong reading file {}", file_name);
+    let file = fs::File::open(file_name).context(format!("unable to read file {}", file_name))?;
+    // SAFETY: the map is only read while parsing and the file is not expected
+    // to be modified by another process in the meantime
+    let data = unsafe { Mmap::map(&file) }.context(error_message.clone())?;
+    parse_binary(&data).context(error_message)
+}
+
+pub fn parse_binary(data: &[u8]) -> Result<Mesh> {
+    ensure!(
+        data.len() >= HEADER_SIZE && &data[..8] == MAGIC,
+        "not a binary mesh file"
+    );
+    let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+    let u64_at = |i: usize| u64::from_le_bytes(data[i..i + 8].try_into().unwrap());
+
+    let version = u32_at(8);
+    ensure!(version == VERSION, "unsupported version {}", version);
+    let flags = u32_at(12);
+    ensure!(
+        flags & !(FLAG_F32 | FLAG_TAGS) == 0,
+        "unknown flags {:#x}",
+        flags
+    );
+    let num_vertices = usize::try_from(u64_at(16))?;
+    let num_triangles = usize::try_from(u64_at(24))?;
+
+    let float_size = if flags & FLAG_F32 != 0 { 4 } else { 8 };
+    let vertices_end = num_vertices
+        .checked_mul(3 * float_size)
+        .and_then(|n| n.checked_add(HEADER_SIZE))
+        .context("vertex count too large")?;
+    let triangles_end = num_triangles
+        .checked_mul(12)
+        .and_then(|n| n.checked_add(vertices_end))
+        .context("triangle count too large")?;
+    let tags_end = if flags & FLAG_TAGS != 0 {
+        triangles_end + 4 * num_triangles
+    } else {
+        triangles_end
+    };
+    ensure!(
+        data.len() == tags_end,
+        "expected {} bytes but file has {}",
+        tags_end,
+        data.len()
+    );
+
+    let float = |b: &[u8]| match float_size {
+        4 => f32::from_le_bytes(b.try_into().unwrap()) as f64,
+        _ => f64::from_le_bytes(b.try_into().unwrap()),
+    };
+    let vertices = data[HEADER_SIZE..vertices_end]
+        .chunks_exact(3 * float_size)
+        .map(|v| Vector3 {
+            x: float(&v[..float_size]),
+            y: float(&v[float_size..2 * float_size]),
+            z: float(&v[2 * float_size..]),
+        })
+        .collect();
+
+    let index = |b: &[u8]| u32::from_le_bytes(b.try_into().unwrap()) as usize;
+    let triangles: Vec<_> = data[vertices_end..triangles_end]
+        .chunks_exact(12)
+        .map(|t| (index(&t[0..4]), index(&t[4..8]), index(&t[8..12])))
+        .collect();
+    ensure!(
+        triangles
+            .iter()
+            .all(|&(a, b, c)| a < num_vertices && b < num_vertices && c < num_vertices),
+        "triangle refers to a missing vertex"
+    );
+
+    let mut mesh = Mesh::new(vertices, triangles);
+    if flags & FLAG_TAGS != 0 {
+        mesh.tags = Some(
+            data[triangles_end..tags_end]
+                .chunks_exact(4)
+                .map(|t| i32::from_le_bytes(t.try_into().unwrap()))
+                .collect(),
+        );
+    }
+
+    Ok(mesh)
+}
+
+/// Writes vertices as f64, or as f32 if `single_precision` is set, and tags
+/// if the mesh has them.
+pub fn write_binary(file_name: &str, mesh: &Mesh, single_precision: bool) -> Result<()> {
+    ensure!(
+        mesh.vertices.len() <= u32::MAX as usize,
+        "too many vertices for 32 bit indices"
+    );
+
+    let error_message = format!("unable to write file {}", file_name);
+    let mut f = BufWriter::new(fs::File::create(file_name).context(error_message.clone())?);
+    write_contents(&mut f, mesh, single_precision)
+        .and_then(|_| f.flush())
+        .context(error_message)
+}
+
+fn write_contents(f: &mut impl Write, mesh: &Mesh, single_precision: bool) -> std::io::Result<()> {
+    let mut flags = 0;
+    if single_precision {
+        flags |= FLAG_F32;
+    }
+    if mesh.tags.is_some() {
+        flags |= FLAG_TAGS;
+    }
+
+    f.write_all(MAGIC)?;
+    f.write_all(&VERSION.to_le_bytes())?;
+    f.write_all(&flags.to_le_bytes())?;
+    f.write_all(&(mesh.vertices.len() as u64).to_le_bytes())?;
+    f.write_all(&(mesh.triangles.len() as u64).to_le_bytes())?;
+
+    for p in &mesh.vertices {
+        for value in [p.x, p.y, p.z] {
+            if single_precision {
+                f.write_all(&(value as f32).to_le_bytes())?;
+            } else {
+                f.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    for &(a, b, c) in &mesh.triangles {
+        for i in [a, b, c] {
+            f.write_all(&(i as u32).to_le_bytes())?;
+        }
+    }
+    if let Some(tags) = &mesh.tags {
+        for tag in tags {
+            f.write_all(&tag.to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh() -> Mesh {
+        let point = |x, y, z| Vector3 { x, y, z };
+        Mesh {
+            vertices: vec![
+                point(0.1, 0.2, 0.3),
+                point(1.0e-7, -2.25, 1.0 / 3.0),
+                point(0.0, 1.0, 0.5),
+            ],
+            triangles: vec![(0, 1, 2), (2, 1, 0)],
+            tags: Some(vec![1005, -1]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let file_name = std::env::temp_dir().join("mesh-binary-round-trip.bmesh");
+        let file_name = file_name.to_str().unwrap();
+        write_binary(file_name, &mesh(), false).unwrap();
+        let read_back = read_binary(file_name).unwrap();
+        fs::remove_file(file_name).unwrap();
+
+        assert_eq!(read_back, mesh());
+    }
+
+    #[test]
+    fn test_single_precision() {
+        let mut data = Vec::new();
+        write_contents(&mut data, &mesh(), true).unwrap();
+        assert_eq!(data.len(), HEADER_SIZE + 3 * 12 + 2 * 12 + 2 * 4);
+
+        let read_back = parse_binary(&data).unwrap();
+        assert_eq!(read_back.vertices[1].y, -2.25);
+        assert_eq!(read_back.vertices[0].x, 0.1_f32 as f64);
+        assert_eq!(read_back.tags, mesh().tags);
+
+        assert!(parse_binary(&data[..data.len() - 1]).is_err());
+    }
+}
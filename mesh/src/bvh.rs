@@ -0,0 +1,362 @@
+use crate::intersection;
+use crate::mesh::Triplet;
+use crate::triangle::Triangle;
+use crate::vector::Vector3;
+
+const MAX_TRIANGLES_PER_LEAF: usize = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    /// Position of the triangle in the triangle list the hierarchy was built from
+    pub triangle: usize,
+    pub distance: f64,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct BoundingBox {
+    pub(crate) min: Vector3,
+    pub(crate) max: Vector3,
+}
+
+impl BoundingBox {
+    fn empty() -> Self {
+        BoundingBox {
+            min: Vector3 {
+                x: f64::MAX,
+                y: f64::MAX,
+                z: f64::MAX,
+            },
+            max: Vector3 {
+                x: -f64::MAX,
+                y: -f64::MAX,
+                z: -f64::MAX,
+            },
+        }
+    }
+
+    fn grow(&mut self, point: &Vector3) {
+        self.min.x = self.min.x.min(point.x);
+        self.min.y = self.min.y.min(point.y);
+        self.min.z = self.min.z.min(point.z);
+        self.max.x = self.max.x.max(point.x);
+        self.max.y = self.max.y.max(point.y);
+        self.max.z = self.max.z.max(point.z);
+    }
+
+    fn longest_axis(&self) -> usize {
+        let extent = [
+            self.max.x - self.min.x,
+            self.max.y - self.min.y,
+            self.max.z - self.min.z,
+        ];
+        if extent[0] >= extent[1] && extent[0] >= extent[2] {
+            0
+        } else if extent[1] >= extent[2] {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test, returns the distance at which the ray enters the box.
+    /// Boundaries count as inside so that grazing rays are not lost.
+    fn entry_distance(&self, origin: &Vector3, direction: &Vector3) -> Option<f64> {
+        let mut t_min = 0.0_f64;
+        let mut t_max = f64::MAX;
+
+        for (o, d, lo, hi) in [
+            (origin.x, direction.x, self.min.x, self.max.x),
+            (origin.y, direction.y, self.min.y, self.max.y),
+            (origin.z, direction.z, self.min.z, self.max.z),
+        ] {
+            if d == 0.0 {
+                if o < lo || o > hi {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (lo - o) / d;
+            let t2 = (hi - o) / d;
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+            if t_min > t_max {
+                return None;
+            }
+        }
+
+        Some(t_min)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum Node {
+    Leaf {
+        bounds: BoundingBox,
+        first: usize,
+        count: usize,
+    },
+    Inner {
+        bounds: BoundingBox,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    pub(crate) fn bounds(&self) -> &BoundingBox {
+        match self {
+            Node::Leaf { bounds, .. } => bounds,
+            Node::Inner { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// Bounding volume hierarchy over the triangles of a mesh, for casting rays
+/// in arbitrary directions.
+pub struct Bvh {
+    /// Parents come before their children, the root is the first node
+    pub(crate) nodes: Vec<Node>,
+    /// Triangles in leaf order, together with their original position
+    pub(crate) triangles: Vec<(usize, Triangle)>,
+}
+
+impl Bvh {
+    pub fn new(coordinates: &[Vector3], triangles: &[Triplet]) -> Self {
+        let mut items: Vec<(usize, Triangle, Vector3)> = triangles
+            .iter()
+            .enumerate()
+            .map(|(i, triplet)| {
+                let triangle = Triangle::from_indices(coordinates, *triplet);
+                (i, triangle, centroid(&triangle))
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        if !items.is_empty() {
+            let n = items.len();
+            build(&mut nodes, &mut items, 0, n);
+        }
+
+        Bvh {
+            nodes,
+            triangles: items.into_iter().map(|(i, t, _)| (i, t)).collect(),
+        }
+    }
+
+    /// Closest intersection along the ray.
+    pub fn first_hit(&self, origin: &Vector3, direction: &Vector3) -> Option<Hit> {
+        let mut best: Option<Hit> = None;
+        self.traverse(origin, direction, |hit| {
+            if best.is_none_or(|b| hit.distance < b.distance) {
+                best = Some(hit);
+            }
+            false
+        });
+        best
+    }
+
+    /// All intersections along the ray, sorted by distance.
+    pub fn all_hits(&self, origin: &Vector3, direction: &Vector3) -> Vec<Hit> {
+        let mut hits = Vec::new();
+        self.traverse(origin, direction, |hit| {
+            hits.push(hit);
+            false
+        });
+        hits.sort_by(|a, b| {
+            a.distance
+                .total_cmp(&b.distance)
+                .then(a.triangle.cmp(&b.triangle))
+        });
+        hits
+    }
+
+    /// Whether the ray hits any triangle, stops at the first hit found.
+    pub fn any_hit(&self, origin: &Vector3, direction: &Vector3) -> bool {
+        let mut found = false;
+        self.traverse(origin, direction, |_| {
+            found = true;
+            true
+        });
+        found
+    }
+
+    /// Calls `on_hit` for each intersected triangle until it returns true.
+    fn traverse<F>(&self, origin: &Vector3, direction: &Vector3, mut on_hit: F)
+    where
+        F: FnMut(Hit) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if node.bounds().entry_distance(origin, direction).is_none() {
+                continue;
+            }
+            match node {
+                Node::Leaf { first, count, .. } => {
+                    for (i, triangle) in &self.triangles[*first..first + count] {
+                        if let Some(distance) =
+                            intersection::ray_triangle_distance(origin, direction, triangle)
+                        {
+                            let hit = Hit {
+                                triangle: *i,
+                                distance,
+                            };
+                            if on_hit(hit) {
+                                return;
+                            }
+                        }
+                    }
+                }
+                Node::Inner { left, right, .. } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+            }
+        }
+    }
+}
+
+fn centroid(triangle: &Triangle) -> Vector3 {
+    Vector3 {
+        x: (triangle.vertex1.x + triangle.vertex2.x + triangle.vertex3.x) / 3.0,
+        y: (triangle.vertex1.y + triangle.vertex2.y + triangle.vertex3.y) / 3.0,
+        z: (triangle.vertex1.z + triangle.vertex2.z + triangle.vertex3.z) / 3.0,
+    }
+}
+
+fn axis_value(point: &Vector3, axis: usize) -> f64 {
+    match axis {
+        0 => point.x,
+        1 => point.y,
+        _ => point.z,
+    }
+}
+
+/// Builds the subtree for items[first..end] and returns its node index.
+/// Splits at the median centroid along the longest axis of the centroids.
+fn build(
+    nodes: &mut Vec<Node>,
+    items: &mut [(usize, Triangle, Vector3)],
+    first: usize,
+    end: usize,
+) -> usize {
+    let mut bounds = BoundingBox::empty();
+    let mut centroid_bounds = BoundingBox::empty();
+    for (_, triangle, center) in &items[first..end] {
+        bounds.grow(&triangle.vertex1);
+        bounds.grow(&triangle.vertex2);
+        bounds.grow(&triangle.vertex3);
+        centroid_bounds.grow(center);
+    }
+
+    let count = end - first;
+    if count <= MAX_TRIANGLES_PER_LEAF {
+        nodes.push(Node::Leaf {
+            bounds,
+            first,
+            count,
+        });
+        return nodes.len() - 1;
+    }
+
+    let axis = centroid_bounds.longest_axis();
+    let middle = first + count / 2;
+    items[first..end].select_nth_unstable_by(count / 2, |a, b| {
+        axis_value(&a.2, axis)
+            .total_cmp(&axis_value(&b.2, axis))
+            .then(a.0.cmp(&b.0))
+    });
+
+    // reserve the slot so that the parent comes before its children
+    let index = nodes.len();
+    nodes.push(Node::Leaf {
+        bounds,
+        first,
+        count,
+    });
+    let left = build(nodes, items, first, middle);
+    let right = build(nodes, items, middle, end);
+    nodes[index] = Node::Inner {
+        bounds,
+        left,
+        right,
+    };
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(x: f64, y: f64, z: f64) -> Vector3 {
+        Vector3 { x, y, z }
+    }
+
+    /// Stack of unit squares at z = 0, 1, ..., n - 1, two triangles each.
+    fn stacked_squares(n: usize) -> (Vec<Vector3>, Vec<Triplet>) {
+        let mut coordinates = Vec::new();
+        let mut triangles = Vec::new();
+        for i in 0..n {
+            let z = i as f64;
+            let o = coordinates.len();
+            coordinates.push(vector(0.0, 0.0, z));
+            coordinates.push(vector(1.0, 0.0, z));
+            coordinates.push(vector(1.0, 1.0, z));
+            coordinates.push(vector(0.0, 1.0, z));
+            triangles.push((o, o + 1, o + 2));
+            triangles.push((o, o + 2, o + 3));
+        }
+        (coordinates, triangles)
+    }
+
+    #[test]
+    fn test_queries() {
+        let (coordinates, triangles) = stacked_squares(10);
+        let bvh = Bvh::new(&coordinates, &triangles);
+
+        let origin = vector(0.3, 0.6, 2.5);
+        let up = vector(0.0, 0.0, 1.0);
+        let down = vector(0.0, 0.0, -1.0);
+
+        let hit = bvh.first_hit(&origin, &up).unwrap();
+        assert_eq!(hit.triangle, 7);
+        assert!((hit.distance - 0.5).abs() < 1.0e-12);
+
+        let hits = bvh.all_hits(&origin, &down);
+        assert_eq!(
+            hits.iter().map(|h| h.triangle).collect::<Vec<_>>(),
+            vec![5, 3, 1]
+        );
+
+        assert!(bvh.any_hit(&origin, &vector(0.1, 0.0, 1.0)));
+        assert!(!bvh.any_hit(&origin, &vector(1.0, 0.0, 0.0)));
+        assert!(!bvh.any_hit(&vector(0.3, 0.6, 9.5), &up));
+    }
+
+    #[test]
+    fn test_same_as_brute_force() {
+        let (coordinates, triangles) = stacked_squares(50);
+        let bvh = Bvh::new(&coordinates, &triangles);
+
+        let origin = vector(0.5, 0.5, -1.0);
+        for i in 0..20 {
+            let direction = vector(0.03 * i as f64, 0.01, 1.0);
+            let brute_force = triangles
+                .iter()
+                .filter(|t| {
+                    intersection::ray_intersects_triangle(
+                        &origin,
+                        &direction,
+                        &Triangle::from_indices(&coordinates, **t),
+                    )
+                })
+                .count();
+            assert_eq!(bvh.all_hits(&origin, &direction).len(), brute_force);
+        }
+    }
+}
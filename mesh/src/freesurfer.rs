@@ -0,0 +1,168 @@
+//! Reading and writing of FreeSurfer binary triangle surfaces such as
+//! `lh.pial`, `rh.white` or `bem/outer_skin.surf`: a magic number, a
+//! creation line, vertex and face counts and big endian coordinates and
+//! indices, optionally followed by volume geometry.
+
+use anyhow::{Context, Result};
+use std::fs;
+
+use crate::mesh::Mesh;
+use crate::vector::Vector3;
+
+const TRIANGLE_MAGIC: [u8; 3] = [0xff, 0xff, 0xfe];
+const QUAD_MAGIC: [u8; 3] = [0xff, 0xff, 0xff];
+
+pub fn read_freesurfer(file_name: &str) -> Result<Mesh> {
+    let data = fs::read(file_name).context(format!("unable to read file {}", file_name))?;
+    parse_freesurfer(&data).context(format!("something went wrong reading file {}", file_name))
+}
+
+/// Parses a triangle surface. Coordinates are kept as stored, usually in
+/// tkregister space, so that writing the mesh again gives the same file
+/// contents. Volume geometry following the faces is ignored.
+pub fn parse_freesurfer(data: &[u8]) -> Result<Mesh> {
+    ensure!(
+        !data.starts_with(&QUAD_MAGIC),
+        "quad surfaces are not supported"
+    );
+    ensure!(
+        data.starts_with(&TRIANGLE_MAGIC),
+        "not a FreeSurfer triangle surface"
+    );
+
+    // the creation line ends with two newlines
+    let header_end = data
+        .windows(2)
+        .position(|w| w == b"\n\n")
+        .context("missing creation line")?
+        + 2;
+
+    let mut position = header_end;
+    let mut int = || -> Result<i32> {
+        let bytes = data
+            .get(position..position + 4)
+            .context("unexpected end of file")?;
+        position += 4;
+        Ok(i32::from_be_bytes(bytes.try_into().unwrap()))
+    };
+    let num_vertices = int()?;
+    let num_faces = int()?;
+    ensure!(
+        num_vertices >= 0 && num_faces >= 0,
+        "negative vertex or face count"
+    );
+    let (num_vertices, num_faces) = (num_vertices as usize, num_faces as usize);
+
+    let vertices_end = position + 12 * num_vertices;
+    let faces_end = vertices_end + 12 * num_faces;
+    ensure!(data.len() >= faces_end, "unexpected end of file");
+
+    let float = |b: &[u8]| f32::from_be_bytes(b.try_into().unwrap()) as f64;
+    let vertices: Vec<Vector3> = data[position..vertices_end]
+        .chunks_exact(12)
+        .map(|v| Vector3 {
+            x: float(&v[0..4]),
+            y: float(&v[4..8]),
+            z: float(&v[8..12]),
+        })
+        .collect();
+
+    let mut triangles = Vec::with_capacity(num_faces);
+    for face in data[vertices_end..faces_end].chunks_exact(12) {
+        let [a, b, c] = [0, 4, 8].map(|i| i32::from_be_bytes(face[i..i + 4].try_into().unwrap()));
+        ensure!(
+            [a, b, c]
+                .iter()
+                .all(|&i| i >= 0 && (i as usize) < num_vertices),
+            "face refers to a missing vertex"
+        );
+        triangles.push((a as usize, b as usize, c as usize));
+    }
+
+    Ok(Mesh::new(vertices, triangles))
+}
+
+/// Writes the coordinates as they are, as 32 bit floats and without volume
+/// geometry.
+pub fn write_freesurfer(file_name: &str, mesh: &Mesh) -> Result<()> {
+    let mut data = TRIANGLE_MAGIC.to_vec();
+    data.extend_from_slice(b"created by mesh\n\n");
+    data.extend_from_slice(&(mesh.vertices.len() as i32).to_be_bytes());
+    data.extend_from_slice(&(mesh.triangles.len() as i32).to_be_bytes());
+    for p in &mesh.vertices {
+        for value in [p.x, p.y, p.z] {
+            data.extend_from_slice(&(value as f32).to_be_bytes());
+        }
+    }
+    for &(a, b, c) in &mesh.triangles {
+        for i in [a, b, c] {
+            data.extend_from_slice(&(i as i32).to_be_bytes());
+        }
+    }
+
+    fs::write(file_name, data).context(format!("unable to write file {}", file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64, z: f64) -> Vector3 {
+        Vector3 { x, y, z }
+    }
+
+    fn triangle() -> Mesh {
+        Mesh::new(
+            vec![
+                point(0.0, 0.0, 0.0),
+                point(1.5, 0.0, -2.25),
+                point(0.0, 1.0, 0.5),
+            ],
+            vec![(0, 1, 2)],
+        )
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let file_name = std::env::temp_dir().join("mesh-freesurfer-round-trip.pial");
+        let file_name = file_name.to_str().unwrap();
+        write_freesurfer(file_name, &triangle()).unwrap();
+        let read_back = read_freesurfer(file_name).unwrap();
+        fs::remove_file(file_name).unwrap();
+
+        assert_eq!(read_back, triangle());
+    }
+
+    /// The file contents of `triangle()` followed by volume geometry as
+    /// FreeSurfer writes it, after the tags `[2, useRealRAS, 20]`.
+    fn with_geometry() -> Vec<u8> {
+        let file_name = std::env::temp_dir().join("mesh-freesurfer-geometry.pial");
+        let file_name = file_name.to_str().unwrap();
+        write_freesurfer(file_name, &triangle()).unwrap();
+        let mut data = fs::read(file_name).unwrap();
+        fs::remove_file(file_name).unwrap();
+
+        for tag in [2i32, 0, 20] {
+            data.extend_from_slice(&tag.to_be_bytes());
+        }
+        data.extend_from_slice(
+            b"valid = 1  # volume info valid\nfilename = ../mri/filled-pretess255.mgz\n\
+              volume = 256 256 256\ncras = 1.0 -2.0 10.0\n",
+        );
+        data
+    }
+
+    #[test]
+    fn test_round_trip_with_geometry() {
+        let data = with_geometry();
+        let mesh = parse_freesurfer(&data).unwrap();
+        assert_eq!(mesh, triangle());
+
+        let file_name = std::env::temp_dir().join("mesh-freesurfer-geometry-round-trip.pial");
+        let file_name = file_name.to_str().unwrap();
+        write_freesurfer(file_name, &mesh).unwrap();
+        let read_back = read_freesurfer(file_name).unwrap();
+        fs::remove_file(file_name).unwrap();
+        assert_eq!(read_back.vertices, mesh.vertices);
+    }
+}
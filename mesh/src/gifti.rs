@@ -0,0 +1,280 @@
+//! Reading and writing of GIfTI surfaces (`.surf.gii`), the format used by
+//! FreeSurfer, Connectome Workbench and nilearn. A surface holds a point set
+//! and a triangle array; other data arrays are skipped when reading.
+
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::{Read, Write};
+
+use crate::mesh::Mesh;
+use crate::vector::Vector3;
+
+const POINTSET: &str = "NIFTI_INTENT_POINTSET";
+const TRIANGLE: &str = "NIFTI_INTENT_TRIANGLE";
+
+/// Coordinates are written as given, in the scanner (world) space of the
+/// volume the mesh was created from, e.g. `final_tissues.nii.gz`.
+const COORDINATE_SPACE: &str = "NIFTI_XFORM_SCANNER_ANAT";
+
+pub fn read_gifti(file_name: &str) -> Result<Mesh> {
+    let contents =
+        fs::read_to_string(file_name).context(format!("unable to read file {}", file_name))?;
+    parse_gifti(&contents).context(format!("something went wrong reading file {}", file_name))
+}
+
+/// Parses a GIfTI surface. Point coordinates are returned as stored, the
+/// coordinate system transform is not applied.
+pub fn parse_gifti(contents: &str) -> Result<Mesh> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let document = roxmltree::Document::parse_with_options(contents, options)?;
+    let root = document.root_element();
+    ensure!(root.has_tag_name("GIFTI"), "not a GIfTI file");
+
+    let mut points = None;
+    let mut triangles = None;
+    for array in root.children().filter(|n| n.has_tag_name("DataArray")) {
+        let intent = array.attribute("Intent").unwrap_or_default();
+        if intent != POINTSET && intent != TRIANGLE {
+            continue;
+        }
+        let (values, rows) = data_array(&array).context(format!("invalid {} array", intent))?;
+        if intent == POINTSET {
+            let p: Vec<Vector3> = values
+                .chunks_exact(3)
+                .map(|c| Vector3 {
+                    x: c[0],
+                    y: c[1],
+                    z: c[2],
+                })
+                .collect();
+            ensure!(p.len() == rows, "point set does not have 3 columns");
+            points.get_or_insert(p);
+        } else {
+            ensure!(
+                values.iter().all(|&i| i >= 0.0),
+                "negative index in triangle array"
+            );
+            let t: Vec<_> = values
+                .chunks_exact(3)
+                .map(|c| (c[0] as usize, c[1] as usize, c[2] as usize))
+                .collect();
+            ensure!(t.len() == rows, "triangle array does not have 3 columns");
+            triangles.get_or_insert(t);
+        }
+    }
+
+    let mesh = Mesh::new(
+        points.context("no point set in file")?,
+        triangles.unwrap_or_default(),
+    );
+    let n = mesh.vertices.len();
+    for &(a, b, c) in &mesh.triangles {
+        ensure!(
+            a < n && b < n && c < n,
+            "triangle refers to a missing point"
+        );
+    }
+
+    Ok(mesh)
+}
+
+/// Values of a two dimensional data array in row major order, and the
+/// number of rows.
+fn data_array(array: &roxmltree::Node) -> Result<(Vec<f64>, usize)> {
+    let attribute = |name: &str| {
+        array
+            .attribute(name)
+            .context(format!("missing attribute {}", name))
+    };
+
+    let rows: usize = attribute("Dim0")?.parse()?;
+    let columns: usize = match attribute("Dimensionality")? {
+        "1" => 1,
+        "2" => attribute("Dim1")?.parse()?,
+        d => bail!("unsupported dimensionality {}", d),
+    };
+    let data = array
+        .children()
+        .find(|n| n.has_tag_name("Data"))
+        .and_then(|n| n.text())
+        .unwrap_or_default();
+
+    let values = match attribute("Encoding")? {
+        "ASCII" => data
+            .split_whitespace()
+            .map(|w| w.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()?,
+        encoding @ ("Base64Binary" | "GZipBase64Binary") => {
+            let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+            let mut bytes = STANDARD.decode(data)?;
+            if encoding == "GZipBase64Binary" {
+                let mut inflated = Vec::new();
+                ZlibDecoder::new(&bytes[..]).read_to_end(&mut inflated)?;
+                bytes = inflated;
+            }
+            let big_endian = match array.attribute("Endian") {
+                Some("BigEndian") => true,
+                Some("LittleEndian") | None => false,
+                Some(e) => bail!("unknown endianness {}", e),
+            };
+            decode(&bytes, attribute("DataType")?, big_endian)?
+        }
+        encoding => bail!("unsupported encoding {}", encoding),
+    };
+    ensure!(
+        values.len() == rows * columns,
+        "expected {} values but found {}",
+        rows * columns,
+        values.len()
+    );
+
+    let values = match array.attribute("ArrayIndexingOrder") {
+        Some("ColumnMajorOrder") => (0..rows * columns)
+            .map(|i| values[(i % columns) * rows + i / columns])
+            .collect(),
+        _ => values,
+    };
+
+    Ok((values, rows))
+}
+
+fn decode(bytes: &[u8], data_type: &str, big_endian: bool) -> Result<Vec<f64>> {
+    macro_rules! numbers {
+        ($t:ty) => {
+            bytes
+                .chunks_exact(std::mem::size_of::<$t>())
+                .map(|b| {
+                    let b = b.try_into().unwrap();
+                    if big_endian {
+                        <$t>::from_be_bytes(b) as f64
+                    } else {
+                        <$t>::from_le_bytes(b) as f64
+                    }
+                })
+                .collect()
+        };
+    }
+
+    Ok(match data_type {
+        "NIFTI_TYPE_UINT8" => bytes.iter().map(|b| *b as f64).collect(),
+        "NIFTI_TYPE_INT32" => numbers!(i32),
+        "NIFTI_TYPE_FLOAT32" => numbers!(f32),
+        "NIFTI_TYPE_FLOAT64" => numbers!(f64),
+        _ => bail!("unsupported data type {}", data_type),
+    })
+}
+
+/// Writes the points as 32 bit floats and the triangles, both zlib
+/// compressed and base64 encoded.
+pub fn write_gifti(file_name: &str, mesh: &Mesh) -> Result<()> {
+    let points: Vec<u8> = mesh
+        .vertices
+        .iter()
+        .flat_map(|p| [p.x, p.y, p.z])
+        .flat_map(|v| (v as f32).to_le_bytes())
+        .collect();
+    let triangles: Vec<u8> = mesh
+        .triangles
+        .iter()
+        .flat_map(|&(a, b, c)| [a, b, c])
+        .flat_map(|i| (i as i32).to_le_bytes())
+        .collect();
+
+    let contents = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE GIFTI SYSTEM "http://www.nitrc.org/frs/download.php/115/gifti.dtd">
+<GIFTI Version="1.0" NumberOfDataArrays="2">
+  <MetaData/>
+  <LabelTable/>
+  <DataArray Intent="{}" DataType="NIFTI_TYPE_FLOAT32" ArrayIndexingOrder="RowMajorOrder" Dimensionality="2" Dim0="{}" Dim1="3" Encoding="GZipBase64Binary" Endian="LittleEndian" ExternalFileName="" ExternalFileOffset="">
+    <MetaData/>
+    <CoordinateSystemTransformMatrix>
+      <DataSpace>{}</DataSpace>
+      <TransformedSpace>{}</TransformedSpace>
+      <MatrixData>1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1</MatrixData>
+    </CoordinateSystemTransformMatrix>
+    <Data>{}</Data>
+  </DataArray>
+  <DataArray Intent="{}" DataType="NIFTI_TYPE_INT32" ArrayIndexingOrder="RowMajorOrder" Dimensionality="2" Dim0="{}" Dim1="3" Encoding="GZipBase64Binary" Endian="LittleEndian" ExternalFileName="" ExternalFileOffset="">
+    <MetaData/>
+    <Data>{}</Data>
+  </DataArray>
+</GIFTI>
+"#,
+        POINTSET,
+        mesh.vertices.len(),
+        COORDINATE_SPACE,
+        COORDINATE_SPACE,
+        compress(&points)?,
+        TRIANGLE,
+        mesh.triangles.len(),
+        compress(&triangles)?,
+    );
+
+    fs::write(file_name, contents).context(format!("unable to write file {}", file_name))
+}
+
+fn compress(bytes: &[u8]) -> Result<String> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    Ok(STANDARD.encode(encoder.finish()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let point = |x, y, z| Vector3 { x, y, z };
+        let mesh = Mesh::new(
+            vec![
+                point(0.0, 0.0, 0.0),
+                point(1.5, 0.0, -2.25),
+                point(0.0, 1.0, 0.5),
+                point(-3.0, 2.0, 1.0),
+            ],
+            vec![(0, 1, 2), (2, 1, 3)],
+        );
+
+        let file_name = std::env::temp_dir().join("mesh-gifti-round-trip.surf.gii");
+        let file_name = file_name.to_str().unwrap();
+        write_gifti(file_name, &mesh).unwrap();
+        let read_back = read_gifti(file_name).unwrap();
+        fs::remove_file(file_name).unwrap();
+
+        assert_eq!(read_back, mesh);
+    }
+
+    #[test]
+    fn test_ascii_column_major() {
+        let contents = r#"<?xml version="1.0" encoding="UTF-8"?>
+<GIFTI Version="1.0" NumberOfDataArrays="2">
+  <DataArray Intent="NIFTI_INTENT_POINTSET" DataType="NIFTI_TYPE_FLOAT32" ArrayIndexingOrder="ColumnMajorOrder" Dimensionality="2" Dim0="3" Dim1="3" Encoding="ASCII">
+    <Data>0 1 0  0 0 1  5 5 5</Data>
+  </DataArray>
+  <DataArray Intent="NIFTI_INTENT_TRIANGLE" DataType="NIFTI_TYPE_INT32" ArrayIndexingOrder="RowMajorOrder" Dimensionality="2" Dim0="1" Dim1="3" Encoding="ASCII">
+    <Data>0 1 2</Data>
+  </DataArray>
+</GIFTI>"#;
+        let mesh = parse_gifti(contents).unwrap();
+
+        assert_eq!(
+            mesh.vertices[1],
+            Vector3 {
+                x: 1.0,
+                y: 0.0,
+                z: 5.0
+            }
+        );
+        assert_eq!(mesh.triangles, vec![(0, 1, 2)]);
+    }
+}
@@ -0,0 +1,491 @@
+//! Reader for Gmsh MSH files, versions 2.2 and 4.1, ASCII and binary.
+//!
+//! Only triangle elements are kept. Each triangle carries its first tag
+//! ("tag1" in SimNIBS), which is the physical (tissue) number.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::str::FromStr;
+
+use crate::mesh::Mesh;
+use crate::vector::Vector3;
+
+const TRIANGLE: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Version {
+    V2,
+    V4,
+}
+
+/// Reads all triangles of a Gmsh file, tagged with their physical tag.
+pub fn read_msh(file_name: &str) -> Result<Mesh> {
+    let data = fs::read(file_name).context(format!("could not read file {}", file_name))?;
+    parse_msh(&data).context(format!("something went wrong reading file {}", file_name))
+}
+
+pub fn parse_msh(data: &[u8]) -> Result<Mesh> {
+    let mut parser = Parser {
+        data,
+        position: 0,
+        binary: false,
+        big_endian: false,
+        size_t: 8,
+    };
+
+    let mut version = None;
+    let mut points = Vec::new();
+    let mut node_index: HashMap<usize, usize> = HashMap::new();
+    let mut surface_tags: HashMap<i32, i32> = HashMap::new();
+    let mut elements = Vec::new();
+
+    while let Some(section) = parser.next_token() {
+        match section {
+            "$MeshFormat" => version = Some(parser.mesh_format()?),
+            "$Entities" if version == Some(Version::V4) => {
+                surface_tags = parser.entities_v4()?;
+            }
+            "$Nodes" => {
+                let v = version.context("$Nodes section found before $MeshFormat")?;
+                (points, node_index) = match v {
+                    Version::V2 => parser.nodes_v2()?,
+                    Version::V4 => parser.nodes_v4()?,
+                };
+            }
+            "$Elements" => {
+                let v = version.context("$Elements section found before $MeshFormat")?;
+                elements = match v {
+                    Version::V2 => parser.elements_v2()?,
+                    Version::V4 => parser.elements_v4(&surface_tags)?,
+                };
+            }
+            _ => {
+                ensure!(section.starts_with('$'), "unexpected token {}", section);
+                parser.skip_section(&section[1..])?;
+            }
+        }
+    }
+
+    let mut triangles = Vec::new();
+    let mut tags = Vec::new();
+    for (nodes, tag) in elements {
+        let mut triangle = [0; 3];
+        for (i, node) in nodes.iter().enumerate() {
+            triangle[i] = *node_index
+                .get(node)
+                .context(format!("element refers to unknown node {}", node))?;
+        }
+        triangles.push((triangle[0], triangle[1], triangle[2]));
+        tags.push(tag);
+    }
+
+    Ok(Mesh {
+        vertices: points,
+        triangles,
+        tags: Some(tags),
+        ..Default::default()
+    })
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    position: usize,
+    binary: bool,
+    big_endian: bool,
+    size_t: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn next_token(&mut self) -> Option<&'a str> {
+        while self.position < self.data.len() && self.data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        let start = self.position;
+        while self.position < self.data.len() && !self.data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        if start == self.position {
+            return None;
+        }
+        std::str::from_utf8(&self.data[start..self.position]).ok()
+    }
+
+    fn token(&mut self) -> Result<&'a str> {
+        self.next_token().context("unexpected end of file")
+    }
+
+    fn parse<T: FromStr>(&mut self) -> Result<T> {
+        let token = self.token()?;
+        token
+            .parse()
+            .ok()
+            .context(format!("could not parse {}", token))
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        let token = self.token()?;
+        ensure!(
+            token == expected,
+            "expected {} but found {}",
+            expected,
+            token
+        );
+        Ok(())
+    }
+
+    /// Moves past the end of the current line, this is where binary data starts.
+    fn end_of_line(&mut self) {
+        while self.position < self.data.len() && self.data[self.position] != b'\n' {
+            self.position += 1;
+        }
+        self.position += 1;
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let end = self.position + N;
+        ensure!(end <= self.data.len(), "unexpected end of binary data");
+        let mut bytes: [u8; N] = self.data[self.position..end].try_into().unwrap();
+        if self.big_endian {
+            bytes.reverse();
+        }
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn int(&mut self) -> Result<i32> {
+        if self.binary {
+            Ok(i32::from_le_bytes(self.bytes()?))
+        } else {
+            self.parse()
+        }
+    }
+
+    fn size(&mut self) -> Result<usize> {
+        if self.binary {
+            if self.size_t == 4 {
+                Ok(u32::from_le_bytes(self.bytes()?) as usize)
+            } else {
+                Ok(u64::from_le_bytes(self.bytes()?) as usize)
+            }
+        } else {
+            self.parse()
+        }
+    }
+
+    fn double(&mut self) -> Result<f64> {
+        if self.binary {
+            Ok(f64::from_le_bytes(self.bytes()?))
+        } else {
+            self.parse()
+        }
+    }
+
+    fn skip_section(&mut self, name: &str) -> Result<()> {
+        let end_marker = format!("$End{}", name);
+        let offset = self.data[self.position..]
+            .windows(end_marker.len())
+            .position(|window| window == end_marker.as_bytes())
+            .context(format!("missing {}", end_marker))?;
+        self.position += offset + end_marker.len();
+        Ok(())
+    }
+
+    fn mesh_format(&mut self) -> Result<Version> {
+        let version = match self.token()? {
+            "2.2" => Version::V2,
+            "4.1" => Version::V4,
+            other => bail!("unsupported MSH version {}", other),
+        };
+        self.binary = self.parse::<u8>()? == 1;
+        self.size_t = self.parse()?;
+        ensure!(
+            self.size_t == 4 || self.size_t == 8,
+            "unsupported data size {}",
+            self.size_t
+        );
+        self.end_of_line();
+
+        if self.binary {
+            // the integer 1 written in the byte order of the file
+            let one: [u8; 4] = self.bytes()?;
+            if i32::from_be_bytes(one) == 1 {
+                self.big_endian = true;
+            } else {
+                ensure!(i32::from_le_bytes(one) == 1, "could not detect byte order");
+            }
+        }
+
+        self.expect("$EndMeshFormat")?;
+        Ok(version)
+    }
+
+    fn nodes_v2(&mut self) -> Result<(Vec<Vector3>, HashMap<usize, usize>)> {
+        // the number of nodes is written as text, also in binary files
+        let n: usize = self.parse()?;
+        self.end_of_line();
+
+        let mut points = Vec::with_capacity(n);
+        let mut node_index = HashMap::with_capacity(n);
+        for i in 0..n {
+            let tag = self.int()? as usize;
+            let x = self.double()?;
+            let y = self.double()?;
+            let z = self.double()?;
+            points.push(Vector3 { x, y, z });
+            node_index.insert(tag, i);
+        }
+
+        self.expect("$EndNodes")?;
+        Ok((points, node_index))
+    }
+
+    fn elements_v2(&mut self) -> Result<Vec<([usize; 3], i32)>> {
+        let n: usize = self.parse()?;
+        self.end_of_line();
+
+        let mut triangles = Vec::new();
+        if self.binary {
+            let mut num_read = 0;
+            while num_read < n {
+                let element_type = self.int()?;
+                let num_elements = self.int()? as usize;
+                let num_tags = self.int()? as usize;
+                let num_nodes = nodes_per_element(element_type)?;
+                for _ in 0..num_elements {
+                    let _id = self.int()?;
+                    let (nodes, tag) = self.element_v2(num_tags, num_nodes)?;
+                    if element_type == TRIANGLE {
+                        triangles.push(([nodes[0], nodes[1], nodes[2]], tag));
+                    }
+                }
+                num_read += num_elements;
+            }
+        } else {
+            for _ in 0..n {
+                let _id: usize = self.parse()?;
+                let element_type = self.int()?;
+                let num_tags = self.int()? as usize;
+                let num_nodes = nodes_per_element(element_type)?;
+                let (nodes, tag) = self.element_v2(num_tags, num_nodes)?;
+                if element_type == TRIANGLE {
+                    triangles.push(([nodes[0], nodes[1], nodes[2]], tag));
+                }
+            }
+        }
+
+        self.expect("$EndElements")?;
+        Ok(triangles)
+    }
+
+    fn element_v2(&mut self, num_tags: usize, num_nodes: usize) -> Result<(Vec<usize>, i32)> {
+        let mut tag = 0;
+        for i in 0..num_tags {
+            let t = self.int()?;
+            if i == 0 {
+                tag = t;
+            }
+        }
+        let mut nodes = Vec::with_capacity(num_nodes);
+        for _ in 0..num_nodes {
+            nodes.push(self.int()? as usize);
+        }
+        Ok((nodes, tag))
+    }
+
+    /// Returns the first physical tag of each surface entity.
+    fn entities_v4(&mut self) -> Result<HashMap<i32, i32>> {
+        self.end_of_line();
+        let num_points = self.size()?;
+        let num_curves = self.size()?;
+        let num_surfaces = self.size()?;
+        let num_volumes = self.size()?;
+
+        let mut surface_tags = HashMap::new();
+        for dimension in 0..4 {
+            let n = [num_points, num_curves, num_surfaces, num_volumes][dimension];
+            for _ in 0..n {
+                let tag = self.int()?;
+                // points have a position, all others a bounding box
+                let num_coordinates = if dimension == 0 { 3 } else { 6 };
+                for _ in 0..num_coordinates {
+                    self.double()?;
+                }
+                let num_physical_tags = self.size()?;
+                for i in 0..num_physical_tags {
+                    let physical_tag = self.int()?;
+                    if dimension == 2 && i == 0 {
+                        surface_tags.insert(tag, physical_tag);
+                    }
+                }
+                if dimension > 0 {
+                    let num_bounding = self.size()?;
+                    for _ in 0..num_bounding {
+                        self.int()?;
+                    }
+                }
+            }
+        }
+
+        self.expect("$EndEntities")?;
+        Ok(surface_tags)
+    }
+
+    fn nodes_v4(&mut self) -> Result<(Vec<Vector3>, HashMap<usize, usize>)> {
+        self.end_of_line();
+        let num_blocks = self.size()?;
+        let n = self.size()?;
+        let _min_tag = self.size()?;
+        let _max_tag = self.size()?;
+
+        let mut points = Vec::with_capacity(n);
+        let mut node_index = HashMap::with_capacity(n);
+        for _ in 0..num_blocks {
+            let dimension = self.int()?;
+            let _entity = self.int()?;
+            let parametric = self.int()? != 0;
+            let num_nodes = self.size()?;
+
+            let mut tags = Vec::with_capacity(num_nodes);
+            for _ in 0..num_nodes {
+                tags.push(self.size()?);
+            }
+            for tag in tags {
+                let x = self.double()?;
+                let y = self.double()?;
+                let z = self.double()?;
+                if parametric {
+                    for _ in 0..dimension {
+                        self.double()?;
+                    }
+                }
+                node_index.insert(tag, points.len());
+                points.push(Vector3 { x, y, z });
+            }
+        }
+
+        self.expect("$EndNodes")?;
+        Ok((points, node_index))
+    }
+
+    fn elements_v4(&mut self, surface_tags: &HashMap<i32, i32>) -> Result<Vec<([usize; 3], i32)>> {
+        self.end_of_line();
+        let num_blocks = self.size()?;
+        let _n = self.size()?;
+        let _min_tag = self.size()?;
+        let _max_tag = self.size()?;
+
+        let mut triangles = Vec::new();
+        for _ in 0..num_blocks {
+            let _dimension = self.int()?;
+            let entity = self.int()?;
+            let element_type = self.int()?;
+            let num_elements = self.size()?;
+            let num_nodes = nodes_per_element(element_type)?;
+
+            // without physical groups we fall back to the entity tag
+            let tag = *surface_tags.get(&entity).unwrap_or(&entity);
+
+            for _ in 0..num_elements {
+                let _id = self.size()?;
+                let nodes = (0..num_nodes)
+                    .map(|_| self.size())
+                    .collect::<Result<Vec<_>>>()?;
+                if element_type == TRIANGLE {
+                    triangles.push(([nodes[0], nodes[1], nodes[2]], tag));
+                }
+            }
+        }
+
+        self.expect("$EndElements")?;
+        Ok(triangles)
+    }
+}
+
+fn nodes_per_element(element_type: i32) -> Result<usize> {
+    let n = match element_type {
+        1 => 2,   // line
+        2 => 3,   // triangle
+        3 => 4,   // quadrangle
+        4 => 4,   // tetrahedron
+        5 => 8,   // hexahedron
+        6 => 6,   // prism
+        7 => 5,   // pyramid
+        8 => 3,   // second order line
+        9 => 6,   // second order triangle
+        10 => 9,  // second order quadrangle
+        11 => 10, // second order tetrahedron
+        12 => 27, // second order hexahedron
+        13 => 18, // second order prism
+        14 => 14, // second order pyramid
+        15 => 1,  // point
+        16 => 8,  // second order quadrangle (serendipity)
+        17 => 20, // second order hexahedron (serendipity)
+        18 => 15, // second order prism (serendipity)
+        19 => 13, // second order pyramid (serendipity)
+        _ => bail!("unsupported element type {}", element_type),
+    };
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_v2() {
+        let data = "$MeshFormat\n2.2 0 8\n$EndMeshFormat\n\
+                    $Nodes\n4\n1 0 0 0\n2 1 0 0\n3 0 1 0\n4 0 0 1\n$EndNodes\n\
+                    $Elements\n3\n\
+                    1 4 2 99 1 1 2 3 4\n\
+                    2 2 2 1005 3 1 2 3\n\
+                    3 2 2 1001 3 2 3 4\n\
+                    $EndElements\n";
+        let mesh = parse_msh(data.as_bytes()).unwrap();
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.triangles, vec![(0, 1, 2), (1, 2, 3)]);
+        assert_eq!(mesh.tags, Some(vec![1005, 1001]));
+    }
+
+    #[test]
+    fn test_binary_v2() {
+        let mut data = b"$MeshFormat\n2.2 1 8\n".to_vec();
+        data.extend(1i32.to_le_bytes());
+        data.extend(b"\n$EndMeshFormat\n$Nodes\n3\n");
+        for (tag, x, y) in [(1i32, 0.0, 0.0), (2, 1.0, 0.0), (3, 0.0, 1.0)] {
+            data.extend(tag.to_le_bytes());
+            for value in [x, y, 0.5f64] {
+                data.extend(value.to_le_bytes());
+            }
+        }
+        data.extend(b"\n$EndNodes\n$Elements\n1\n");
+        for value in [2i32, 1, 2, 1, 1005, 7, 1, 2, 3] {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend(b"\n$EndElements\n");
+
+        let mesh = parse_msh(&data).unwrap();
+        assert_eq!(
+            mesh.vertices[1],
+            Vector3 {
+                x: 1.0,
+                y: 0.0,
+                z: 0.5
+            }
+        );
+        assert_eq!(mesh.triangles, vec![(0, 1, 2)]);
+        assert_eq!(mesh.tags, Some(vec![1005]));
+    }
+
+    #[test]
+    fn test_ascii_v4() {
+        let data = "$MeshFormat\n4.1 0 8\n$EndMeshFormat\n\
+                    $Entities\n0 0 1 0\n7 0 0 0 1 1 0 1 1002 0\n$EndEntities\n\
+                    $Nodes\n1 3 1 3\n2 7 0 3\n10\n20\n30\n0 0 0\n1 0 0\n0 1 0\n$EndNodes\n\
+                    $Elements\n1 1 1 1\n2 7 2 1\n1 30 20 10\n$EndElements\n";
+        let mesh = parse_msh(data.as_bytes()).unwrap();
+        assert_eq!(mesh.vertices.len(), 3);
+        assert_eq!(mesh.triangles, vec![(2, 1, 0)]);
+        assert_eq!(mesh.tags, Some(vec![1002]));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::mesh::Triplet;
+use crate::triangle::Triangle;
+use crate::vector::{vec_minus_vec, Vector3};
+
+pub fn ray_intersects_batch(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    coordinates: &[Vector3],
+    triangles: &[Triplet],
+) -> bool {
+    for triangle in triangles {
+        let triangle = Triangle::from_indices(coordinates, *triangle);
+        if ray_intersects_triangle(ray_origin, ray_direction, &triangle) {
+            return true;
+        }
+    }
+
+    false
+}
+
+pub fn ray_intersects_triangle(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    triangle: &Triangle,
+) -> bool {
+    ray_triangle_distance(ray_origin, ray_direction, triangle).is_some()
+}
+
+// written following Woop, Benthin and Wald (2013), "Watertight ray/triangle
+// intersection", Journal of Computer Graphics Techniques 2(1)
+/// Returns the distance along the ray (in units of the direction length) to
+/// the intersection point, if any.
+///
+/// A ray passing exactly through an edge or a vertex is resolved by
+/// symbolically shifting the ray origin by (e, e^2) in the projected plane,
+/// so that it hits exactly one of the triangles sharing that edge or vertex.
+pub fn ray_triangle_distance(
+    ray_origin: &Vector3,
+    ray_direction: &Vector3,
+    triangle: &Triangle,
+) -> Option<f64> {
+    // hits closer than this to the origin are ignored, so that a ray starting
+    // at a vertex does not hit the triangles of that vertex
+    let epsilon = 0.0000001;
+
+    // permute axes so that the ray mostly points along the new z
+    let d = [ray_direction.x, ray_direction.y, ray_direction.z];
+    let kz = (0..3)
+        .max_by(|&i, &j| d[i].abs().total_cmp(&d[j].abs()))
+        .unwrap();
+    if d[kz] == 0.0 {
+        return None;
+    }
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if d[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // shear so that the ray becomes the positive z axis
+    let sx = d[kx] / d[kz];
+    let sy = d[ky] / d[kz];
+    let sz = 1.0 / d[kz];
+
+    let transform = |vertex: &Vector3| {
+        let v = vec_minus_vec(vertex, ray_origin);
+        let v = [v.x, v.y, v.z];
+        (v[kx] - sx * v[kz], v[ky] - sy * v[kz], sz * v[kz])
+    };
+    let (ax, ay, az) = transform(&triangle.vertex1);
+    let (bx, by, bz) = transform(&triangle.vertex2);
+    let (cx, cy, cz) = transform(&triangle.vertex3);
+
+    // scaled barycentric coordinates
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+
+    let su = edge_sign(u, (bx, by), (cx, cy));
+    let sv = edge_sign(v, (cx, cy), (ax, ay));
+    let sw = edge_sign(w, (ax, ay), (bx, by));
+    if !((su > 0.0 && sv > 0.0 && sw > 0.0) || (su < 0.0 && sv < 0.0 && sw < 0.0)) {
+        return None;
+    }
+
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t = (u * az + v * bz + w * cz) / det;
+
+    if t > epsilon {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// Sign of the edge function `value` for the edge from `p` to `q`. If the
+/// ray passes exactly through the edge, the sign the edge function takes
+/// after moving the origin by (e, e^2) is used instead. Swapping `p` and `q`
+/// flips the result, so the two triangles of a shared edge disagree.
+fn edge_sign(value: f64, p: (f64, f64), q: (f64, f64)) -> f64 {
+    if value != 0.0 {
+        return value.signum();
+    }
+    let dy = q.1 - p.1;
+    let dx = q.0 - p.0;
+    if dy != 0.0 {
+        dy.signum()
+    } else if dx != 0.0 {
+        -dx.signum()
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64, z: f64) -> Vector3 {
+        Vector3 { x, y, z }
+    }
+
+    fn number_of_hits(
+        origin: &Vector3,
+        direction: &Vector3,
+        coordinates: &[Vector3],
+        triangles: &[Triplet],
+    ) -> usize {
+        triangles
+            .iter()
+            .filter(|t| {
+                let triangle = Triangle::from_indices(coordinates, **t);
+                ray_intersects_triangle(origin, direction, &triangle)
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_hit_and_miss() {
+        let triangle = Triangle {
+            vertex1: point(0.0, 0.0, 1.0),
+            vertex2: point(1.0, 0.0, 1.0),
+            vertex3: point(0.0, 1.0, 1.0),
+        };
+        let up = point(0.0, 0.0, 1.0);
+        let down = point(0.0, 0.0, -1.0);
+
+        let distance = ray_triangle_distance(&point(0.2, 0.2, 0.0), &up, &triangle);
+        assert!((distance.unwrap() - 1.0).abs() < 1.0e-12);
+        assert!(ray_triangle_distance(&point(0.2, 0.2, 0.0), &down, &triangle).is_none());
+        assert!(ray_triangle_distance(&point(0.8, 0.8, 0.0), &up, &triangle).is_none());
+    }
+
+    #[test]
+    fn test_shared_edge_is_hit_once() {
+        // unit square split along its diagonal, with the second triangle
+        // given in both orientations
+        let coordinates = vec![
+            point(0.0, 0.0, 0.0),
+            point(1.0, 0.0, 0.0),
+            point(1.0, 1.0, 0.0),
+            point(0.0, 1.0, 0.0),
+        ];
+        for direction in [point(0.0, 0.0, 1.0), point(0.0, 0.0, -1.0)] {
+            let origin = point(0.5, 0.5, -direction.z);
+            for triangles in [[(0, 1, 2), (0, 2, 3)], [(0, 1, 2), (0, 3, 2)]] {
+                assert_eq!(
+                    number_of_hits(&origin, &direction, &coordinates, &triangles),
+                    1
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_shared_vertex_is_hit_once() {
+        // fan of six triangles around the center vertex, one of them flipped
+        let mut coordinates = vec![point(0.0, 0.0, 0.0)];
+        for i in 0..6 {
+            let phi = std::f64::consts::PI * i as f64 / 3.0;
+            coordinates.push(point(phi.cos(), phi.sin(), 0.0));
+        }
+        let mut triangles: Vec<Triplet> = (0..6).map(|i| (0, 1 + i, 1 + (i + 1) % 6)).collect();
+        triangles[2] = (0, 4, 3);
+
+        let origin = point(0.0, 0.0, -1.0);
+        let direction = point(0.0, 0.0, 1.0);
+        assert_eq!(
+            number_of_hits(&origin, &direction, &coordinates, &triangles),
+            1
+        );
+    }
+}
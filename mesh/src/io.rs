@@ -0,0 +1,229 @@
+use anyhow::Result;
+use std::str::FromStr;
+
+use crate::mesh::Mesh;
+use crate::{binary, freesurfer, gifti, gmsh, obj, ply, stl, text, vtk};
+
+/// File formats for meshes, given on the command line as `text`, `msh`,
+/// `stl` (binary), `stl-ascii`, `obj`, `ply` (binary), `ply-ascii`, `vtk`,
+/// `vtp`, `gifti`, `freesurfer`, `binary` or `binary-f32`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Number of points, points, number of triangles, triangles
+    Text,
+    /// Gmsh MSH, read only
+    Msh,
+    /// Binary STL; ASCII STL is recognized as well when reading
+    Stl,
+    /// ASCII STL
+    StlAscii,
+    /// Wavefront OBJ with optional normals and colors
+    Obj,
+    /// Binary little endian PLY; ASCII PLY is recognized as well when reading
+    Ply,
+    /// ASCII PLY
+    PlyAscii,
+    /// Legacy VTK PolyData, write only
+    Vtk,
+    /// XML VTK PolyData, write only
+    Vtp,
+    /// GIfTI surface
+    Gifti,
+    /// FreeSurfer binary triangle surface
+    FreeSurfer,
+    /// Compact binary format with f64 vertices, see `binary`
+    Binary,
+    /// Compact binary format with f32 vertices
+    BinaryF32,
+}
+
+impl Format {
+    /// Guesses the format from the file extension, defaulting to text.
+    /// FreeSurfer surfaces are recognized by their usual names, e.g.
+    /// `lh.pial` or `outer_skin.surf`.
+    pub fn from_file_name(file_name: &str) -> Format {
+        let extension = std::path::Path::new(file_name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("msh") => Format::Msh,
+            Some("stl") => Format::Stl,
+            Some("obj") => Format::Obj,
+            Some("ply") => Format::Ply,
+            Some("vtk") => Format::Vtk,
+            Some("vtp") => Format::Vtp,
+            Some("gii") => Format::Gifti,
+            Some("bmesh") => Format::Binary,
+            Some("surf" | "pial" | "white" | "inflated" | "orig" | "smoothwm" | "sphere") => {
+                Format::FreeSurfer
+            }
+            _ => Format::Text,
+        }
+    }
+
+    /// Extension for files written in this format, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Text => "txt",
+            Format::Msh => "msh",
+            Format::Stl | Format::StlAscii => "stl",
+            Format::Obj => "obj",
+            Format::Ply | Format::PlyAscii => "ply",
+            Format::Vtk => "vtk",
+            Format::Vtp => "vtp",
+            Format::Gifti => "surf.gii",
+            Format::FreeSurfer => "surf",
+            Format::Binary | Format::BinaryF32 => "bmesh",
+        }
+    }
+
+    /// Whether vertex and triangle scalars are written. PLY only writes
+    /// vertex scalars, the other formats none.
+    pub fn writes_scalars(&self) -> bool {
+        matches!(self, Format::Vtk | Format::Vtp)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "msh" => Ok(Format::Msh),
+            "stl" => Ok(Format::Stl),
+            "stl-ascii" => Ok(Format::StlAscii),
+            "obj" => Ok(Format::Obj),
+            "ply" => Ok(Format::Ply),
+            "ply-ascii" => Ok(Format::PlyAscii),
+            "vtk" => Ok(Format::Vtk),
+            "vtp" => Ok(Format::Vtp),
+            "gifti" => Ok(Format::Gifti),
+            "freesurfer" => Ok(Format::FreeSurfer),
+            "binary" => Ok(Format::Binary),
+            "binary-f32" => Ok(Format::BinaryF32),
+            _ => Err(format!(
+                "expected text, msh, stl, stl-ascii, obj, ply, ply-ascii, vtk, vtp, gifti, freesurfer, binary or binary-f32 but got {}",
+                s
+            )),
+        }
+    }
+}
+
+/// Reads a mesh in the format given by the file extension.
+pub fn read_mesh(file_name: &str) -> Result<Mesh> {
+    read_mesh_as(file_name, Format::from_file_name(file_name))
+}
+
+pub fn read_mesh_as(file_name: &str, format: Format) -> Result<Mesh> {
+    read_mesh_indexed_from(file_name, format, 0)
+}
+
+/// Reads a mesh whose triangle indices start at `first_index`, 0 or 1 (as in
+/// Gmsh). Only text files can be read with 1-based indices; all other
+/// formats define how they count.
+pub fn read_mesh_indexed_from(file_name: &str, format: Format, first_index: usize) -> Result<Mesh> {
+    ensure!(
+        first_index == 0 || format == Format::Text,
+        "1-based indices are only supported for the text format"
+    );
+    match format {
+        Format::Text => text::read_text(file_name, first_index),
+        Format::Msh => gmsh::read_msh(file_name),
+        Format::Stl | Format::StlAscii => stl::read_stl(file_name),
+        Format::Obj => obj::read_obj(file_name),
+        Format::Ply | Format::PlyAscii => ply::read_ply(file_name),
+        Format::Vtk | Format::Vtp => bail!("reading VTK files is not supported"),
+        Format::Gifti => gifti::read_gifti(file_name),
+        Format::FreeSurfer => freesurfer::read_freesurfer(file_name),
+        Format::Binary | Format::BinaryF32 => binary::read_binary(file_name),
+    }
+}
+
+/// Like [`read_mesh_indexed_from`], but text files may have triangles which
+/// refer to missing vertices, e.g. to report them when validating a mesh.
+/// Readers of the other formats still fail on such triangles.
+pub fn read_mesh_unchecked(file_name: &str, format: Format, first_index: usize) -> Result<Mesh> {
+    match format {
+        Format::Text => text::read_text_unchecked(file_name, first_index),
+        _ => read_mesh_indexed_from(file_name, format, first_index),
+    }
+}
+
+/// Writes a mesh in the format given by the file extension.
+pub fn write_mesh(file_name: &str, mesh: &Mesh) -> Result<()> {
+    write_mesh_as(file_name, mesh, Format::from_file_name(file_name))
+}
+
+pub fn write_mesh_as(file_name: &str, mesh: &Mesh, format: Format) -> Result<()> {
+    match format {
+        Format::Text => text::write_text(file_name, mesh),
+        Format::Msh => bail!("writing Gmsh files is not supported"),
+        Format::Stl => stl::write_stl(file_name, mesh, true),
+        Format::StlAscii => stl::write_stl(file_name, mesh, false),
+        Format::Obj => obj::write_obj(file_name, mesh),
+        Format::Ply => ply::write_ply(file_name, mesh, true),
+        Format::PlyAscii => ply::write_ply(file_name, mesh, false),
+        Format::Vtk => vtk::write_vtk(file_name, mesh),
+        Format::Vtp => vtk::write_vtp(file_name, mesh),
+        Format::Gifti => gifti::write_gifti(file_name, mesh),
+        Format::FreeSurfer => freesurfer::write_freesurfer(file_name, mesh),
+        Format::Binary => binary::write_binary(file_name, mesh, false),
+        Format::BinaryF32 => binary::write_binary(file_name, mesh, true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Vector3;
+    use std::fs;
+
+    #[test]
+    fn test_round_trip() {
+        let mesh = Mesh::new(
+            vec![
+                Vector3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                Vector3 {
+                    x: 1.5,
+                    y: -2.25,
+                    z: 1e-7,
+                },
+                Vector3 {
+                    x: 0.1,
+                    y: 0.2,
+                    z: 0.3,
+                },
+            ],
+            vec![(0, 1, 2), (2, 1, 0)],
+        );
+
+        let file_name = std::env::temp_dir().join("mesh-io-round-trip.txt");
+        let file_name = file_name.to_str().unwrap();
+        write_mesh(file_name, &mesh).unwrap();
+        let read_back = read_mesh(file_name).unwrap();
+        fs::remove_file(file_name).unwrap();
+
+        assert_eq!(read_back, mesh);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Format::from_file_name("a/b.msh"), Format::Msh);
+        assert_eq!(Format::from_file_name("outside.STL"), Format::Stl);
+        assert_eq!(Format::from_file_name("1001.txt"), Format::Text);
+        assert_eq!(Format::from_file_name("pial.ply"), Format::Ply);
+        assert_eq!(Format::from_file_name("lh.pial.surf.gii"), Format::Gifti);
+        assert_eq!(Format::from_file_name("surf/rh.white"), Format::FreeSurfer);
+        assert_eq!(Format::from_file_name("all.bmesh"), Format::Binary);
+        assert_eq!("stl-ascii".parse(), Ok(Format::StlAscii));
+        assert!("vtu".parse::<Format>().is_err());
+        assert!(Format::from_file_name("annotated.vtp").writes_scalars());
+        assert!(!Format::from_file_name("annotated.ply").writes_scalars());
+    }
+}
@@ -0,0 +1,21 @@
+pub mod binary;
+pub mod bvh;
+pub mod freesurfer;
+pub mod gifti;
+pub mod gmsh;
+pub mod intersection;
+pub mod io;
+mod mesh;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+pub mod text;
+pub mod triangle;
+pub mod vector;
+pub mod vtk;
+pub mod winding;
+
+pub use crate::mesh::{Mesh, Triplet};
+
+#[macro_use]
+extern crate anyhow;
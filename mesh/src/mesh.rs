@@ -0,0 +1,195 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::vector::Vector3;
+
+pub type Triplet = (usize, usize, usize);
+
+/// Triangle surface mesh with indices into `vertices`.
+///
+/// `tags`, if present, holds one tag per triangle (e.g. the tissue number
+/// from a Gmsh file). `normals`, `colors` and `vertex_scalars` hold optional
+/// per-vertex attributes as read from OBJ or PLY files, `triangle_scalars`
+/// per-triangle values such as a classification result.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Vector3>,
+    pub triangles: Vec<Triplet>,
+    pub tags: Option<Vec<i32>>,
+    pub normals: Option<Vec<Vector3>>,
+    pub colors: Option<Vec<[u8; 3]>>,
+    /// Named per-vertex values, e.g. thickness or curvature.
+    pub vertex_scalars: Vec<(String, Vec<f64>)>,
+    /// Named per-triangle values.
+    pub triangle_scalars: Vec<(String, Vec<f64>)>,
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Vector3>, triangles: Vec<Triplet>) -> Self {
+        Mesh {
+            vertices,
+            triangles,
+            ..Default::default()
+        }
+    }
+
+    /// Returns a copy without vertices that no triangle refers to.
+    /// Remaining vertices keep their relative order and attributes.
+    pub fn remove_unreferenced_vertices(&self) -> Mesh {
+        let mut used_indices: Vec<usize> = self
+            .triangles
+            .iter()
+            .flat_map(|&(a, b, c)| vec![a, b, c])
+            .collect();
+        used_indices.sort_unstable();
+        used_indices.dedup();
+
+        let mut new_vertices = Vec::new();
+        let mut point_index_map: HashMap<usize, usize> = HashMap::new();
+        for (i, j) in used_indices.iter().enumerate() {
+            point_index_map.insert(*j, i);
+            new_vertices.push(self.vertices[*j]);
+        }
+
+        let new_triangles = self
+            .triangles
+            .iter()
+            .map(|(a, b, c)| (point_index_map[a], point_index_map[b], point_index_map[c]))
+            .collect();
+
+        Mesh {
+            vertices: new_vertices,
+            triangles: new_triangles,
+            tags: self.tags.clone(),
+            normals: self.normals.as_ref().map(|n| select(n, &used_indices)),
+            colors: self.colors.as_ref().map(|c| select(c, &used_indices)),
+            vertex_scalars: self
+                .vertex_scalars
+                .iter()
+                .map(|(name, values)| (name.clone(), select(values, &used_indices)))
+                .collect(),
+            triangle_scalars: self.triangle_scalars.clone(),
+        }
+    }
+
+    /// Returns a copy where only the first occurrence of each triangle is
+    /// kept.
+    pub fn remove_duplicate_triangles(&self) -> Mesh {
+        let mut seen: HashSet<Triplet> = HashSet::new();
+        self.filter_triangles(|_, triangle| seen.insert(*triangle))
+    }
+
+    /// Returns the triangles for which `keep(index, triangle)` is true,
+    /// together with their tags and scalars. Vertices and their attributes
+    /// are kept.
+    pub fn filter_triangles<F>(&self, mut keep: F) -> Mesh
+    where
+        F: FnMut(usize, &Triplet) -> bool,
+    {
+        let kept: Vec<usize> = self
+            .triangles
+            .iter()
+            .enumerate()
+            .filter(|(i, triangle)| keep(*i, triangle))
+            .map(|(i, _)| i)
+            .collect();
+        self.select_triangles(&kept)
+    }
+
+    /// Returns the triangles at `indices`, in that order and possibly
+    /// repeated, together with their tags and scalars. Vertices and their
+    /// attributes are kept.
+    pub fn select_triangles(&self, indices: &[usize]) -> Mesh {
+        Mesh {
+            vertices: self.vertices.clone(),
+            triangles: select(&self.triangles, indices),
+            tags: self.tags.as_ref().map(|t| select(t, indices)),
+            normals: self.normals.clone(),
+            colors: self.colors.clone(),
+            vertex_scalars: self.vertex_scalars.clone(),
+            triangle_scalars: self
+                .triangle_scalars
+                .iter()
+                .map(|(name, values)| (name.clone(), select(values, indices)))
+                .collect(),
+        }
+    }
+
+    /// Returns a copy whose vertices are the ones at `indices`, in that order
+    /// and possibly repeated, together with their attributes. Triangles are
+    /// kept as they are.
+    pub fn select_vertices(&self, indices: &[usize]) -> Mesh {
+        Mesh {
+            vertices: select(&self.vertices, indices),
+            triangles: self.triangles.clone(),
+            tags: self.tags.clone(),
+            normals: self.normals.as_ref().map(|n| select(n, indices)),
+            colors: self.colors.as_ref().map(|c| select(c, indices)),
+            vertex_scalars: self
+                .vertex_scalars
+                .iter()
+                .map(|(name, values)| (name.clone(), select(values, indices)))
+                .collect(),
+            triangle_scalars: self.triangle_scalars.clone(),
+        }
+    }
+}
+
+fn select<T: Copy>(values: &[T], indices: &[usize]) -> Vec<T> {
+    indices.iter().map(|i| values[*i]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64) -> Vector3 {
+        Vector3 { x, y: 0.0, z: 0.0 }
+    }
+
+    #[test]
+    fn test_remove_unreferenced_vertices() {
+        let mesh = Mesh {
+            vertices: (0..6).map(|i| point(i as f64)).collect(),
+            triangles: vec![(5, 1, 3), (3, 1, 4)],
+            tags: Some(vec![7, 8]),
+            vertex_scalars: vec![("x".to_string(), (0..6).map(|i| i as f64).collect())],
+            ..Default::default()
+        };
+        let mesh = mesh.remove_unreferenced_vertices();
+
+        assert_eq!(
+            mesh.vertices,
+            vec![point(1.0), point(3.0), point(4.0), point(5.0)]
+        );
+        assert_eq!(mesh.triangles, vec![(3, 0, 1), (1, 0, 2)]);
+        assert_eq!(mesh.tags, Some(vec![7, 8]));
+        assert_eq!(mesh.vertex_scalars[0].1, vec![1.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn test_filter_triangles() {
+        let mesh = Mesh {
+            vertices: (0..4).map(|i| point(i as f64)).collect(),
+            triangles: vec![(0, 1, 2), (1, 2, 3), (0, 2, 3)],
+            tags: Some(vec![1, 2, 3]),
+            triangle_scalars: vec![("y".to_string(), vec![4.0, 5.0, 6.0])],
+            ..Default::default()
+        };
+        let mesh = mesh.filter_triangles(|_, &(a, _, _)| a == 0);
+
+        assert_eq!(mesh.triangles, vec![(0, 1, 2), (0, 2, 3)]);
+        assert_eq!(mesh.tags, Some(vec![1, 3]));
+        assert_eq!(mesh.triangle_scalars[0].1, vec![4.0, 6.0]);
+    }
+
+    #[test]
+    fn test_remove_duplicate_triangles() {
+        let mesh = Mesh::new(
+            (0..4).map(|i| point(i as f64)).collect(),
+            vec![(0, 1, 2), (1, 2, 3), (0, 1, 2), (2, 1, 0)],
+        );
+        let mesh = mesh.remove_duplicate_triangles();
+
+        assert_eq!(mesh.triangles, vec![(0, 1, 2), (1, 2, 3), (2, 1, 0)]);
+    }
+}
@@ -0,0 +1,212 @@
+//! Reading and writing of Wavefront OBJ files. Only vertices, vertex normals
+//! and faces are used; texture coordinates, groups and materials are
+//! skipped. Vertex colors follow the common `v x y z r g b` extension with
+//! components between 0 and 1.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{BufWriter, Write};
+
+use crate::mesh::Mesh;
+use crate::vector::Vector3;
+
+pub fn read_obj(file_name: &str) -> Result<Mesh> {
+    let contents =
+        fs::read_to_string(file_name).context(format!("unable to read file {}", file_name))?;
+    parse_obj(&contents).context(format!("something went wrong reading file {}", file_name))
+}
+
+pub fn parse_obj(contents: &str) -> Result<Mesh> {
+    let mut vertices = Vec::new();
+    let mut colors = Vec::new();
+    let mut normals = Vec::new();
+    let mut vertex_normals: Vec<Option<Vector3>> = Vec::new();
+    let mut triangles = Vec::new();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line_number = line_number + 1;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let numbers = || {
+            words[1..]
+                .iter()
+                .map(|w| w.parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .context(format!("invalid number on line {}", line_number))
+        };
+
+        match words.first() {
+            Some(&"v") => {
+                let values = numbers()?;
+                ensure!(
+                    values.len() == 3 || values.len() == 6,
+                    "expected 3 coordinates and optionally 3 colors on line {}",
+                    line_number
+                );
+                vertices.push(Vector3 {
+                    x: values[0],
+                    y: values[1],
+                    z: values[2],
+                });
+                if values.len() == 6 {
+                    colors
+                        .push([3, 4, 5].map(|i| (values[i].clamp(0.0, 1.0) * 255.0).round() as u8));
+                }
+                vertex_normals.push(None);
+            }
+            Some(&"vn") => {
+                let values = numbers()?;
+                ensure!(
+                    values.len() == 3,
+                    "expected 3 normal components on line {}",
+                    line_number
+                );
+                normals.push(Vector3 {
+                    x: values[0],
+                    y: values[1],
+                    z: values[2],
+                });
+            }
+            Some(&"f") => {
+                let mut face = Vec::new();
+                for word in &words[1..] {
+                    let mut parts = word.split('/');
+                    let vertex = index(parts.next(), vertices.len(), line_number)?;
+                    let normal = parts.nth(1).filter(|p| !p.is_empty());
+                    if normal.is_some() {
+                        let normal = index(normal, normals.len(), line_number)?;
+                        vertex_normals[vertex] = Some(normals[normal]);
+                    }
+                    face.push(vertex);
+                }
+                ensure!(
+                    face.len() >= 3,
+                    "face on line {} has fewer than 3 vertices",
+                    line_number
+                );
+                // polygons are split into a fan of triangles
+                for i in 1..face.len() - 1 {
+                    triangles.push((face[0], face[i], face[i + 1]));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut mesh = Mesh::new(vertices, triangles);
+    if !colors.is_empty() {
+        ensure!(
+            colors.len() == mesh.vertices.len(),
+            "only some vertices have colors"
+        );
+        mesh.colors = Some(colors);
+    }
+    if !normals.is_empty() {
+        mesh.normals = vertex_normals.into_iter().collect();
+        if mesh.normals.is_none() && normals.len() == mesh.vertices.len() {
+            // faces without normal indices, assume one normal per vertex
+            mesh.normals = Some(normals);
+        }
+    }
+
+    Ok(mesh)
+}
+
+/// Converts a 1-based (or, if negative, relative to the end) OBJ index.
+fn index(word: Option<&str>, len: usize, line_number: usize) -> Result<usize> {
+    let i: i64 = word
+        .unwrap_or_default()
+        .parse()
+        .context(format!("invalid index on line {}", line_number))?;
+    let i = if i < 0 { len as i64 + i } else { i - 1 };
+    ensure!(
+        (0..len as i64).contains(&i),
+        "index out of range on line {}",
+        line_number
+    );
+    Ok(i as usize)
+}
+
+/// Writes vertices, optional normals and colors, and triangles. Scalars have
+/// no place in OBJ and are left out.
+pub fn write_obj(file_name: &str, mesh: &Mesh) -> Result<()> {
+    let error_message = format!("unable to write file {}", file_name);
+    let mut f = BufWriter::new(fs::File::create(file_name).context(error_message.clone())?);
+    write_contents(&mut f, mesh)
+        .and_then(|_| f.flush())
+        .context(error_message)
+}
+
+fn write_contents(f: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+    for (i, p) in mesh.vertices.iter().enumerate() {
+        write!(f, "v {} {} {}", p.x, p.y, p.z)?;
+        if let Some(colors) = &mesh.colors {
+            let [r, g, b] = colors[i].map(|c| c as f64 / 255.0);
+            write!(f, " {} {} {}", r, g, b)?;
+        }
+        writeln!(f)?;
+    }
+
+    if let Some(normals) = &mesh.normals {
+        for n in normals {
+            writeln!(f, "vn {} {} {}", n.x, n.y, n.z)?;
+        }
+    }
+
+    for (a, b, c) in &mesh.triangles {
+        let [a, b, c] = [a + 1, b + 1, c + 1];
+        if mesh.normals.is_some() {
+            writeln!(f, "f {}//{} {}//{} {}//{}", a, a, b, b, c, c)?;
+        } else {
+            writeln!(f, "f {} {} {}", a, b, c)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_obj() {
+        let contents = "# quad with normals given per corner
+v 0 0 0 1 0 0
+v 1 0 0 0 1 0
+v 1 1 0 0 0 1
+v 0 1 0 1 1 1
+vn 0 0 1
+vt 0.5 0.5
+f 1/1/1 2/1/1 3/1/1 -1/1/1
+";
+        let mesh = parse_obj(contents).unwrap();
+
+        assert_eq!(mesh.triangles, vec![(0, 1, 2), (0, 2, 3)]);
+        assert_eq!(mesh.colors.unwrap()[3], [255, 255, 255]);
+        assert_eq!(mesh.normals.unwrap()[2].z, 1.0);
+
+        assert!(parse_obj("v 0 0 0\nf 1 2 3\n").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let point = |x, y, z| Vector3 { x, y, z };
+        let mut mesh = Mesh::new(
+            vec![
+                point(0.0, 0.0, 0.0),
+                point(1.5, 0.0, 0.0),
+                point(0.0, 1.0, -2.0),
+            ],
+            vec![(0, 1, 2), (2, 1, 0)],
+        );
+        mesh.normals = Some(vec![point(0.0, 0.0, 1.0); 3]);
+        mesh.colors = Some(vec![[0, 128, 255], [1, 2, 3], [255, 255, 255]]);
+
+        let file_name = std::env::temp_dir().join("mesh-obj-round-trip.obj");
+        let file_name = file_name.to_str().unwrap();
+        write_obj(file_name, &mesh).unwrap();
+        let read_back = read_obj(file_name).unwrap();
+        fs::remove_file(file_name).unwrap();
+
+        assert_eq!(read_back, mesh);
+    }
+}
@@ -0,0 +1,487 @@
+//! Reading and writing of PLY files in ASCII and binary little endian
+//! encoding. Vertex properties `nx`, `ny`, `nz` become normals, `red`,
+//! `green`, `blue` colors and any other scalar vertex property a named
+//! scalar. Polygonal faces are split into triangles, other elements are
+//! skipped.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{BufWriter, Write};
+
+use crate::mesh::Mesh;
+use crate::vector::Vector3;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Type {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl Type {
+    fn from_name(name: &str) -> Result<Type> {
+        Ok(match name {
+            "char" | "int8" => Type::Char,
+            "uchar" | "uint8" => Type::UChar,
+            "short" | "int16" => Type::Short,
+            "ushort" | "uint16" => Type::UShort,
+            "int" | "int32" => Type::Int,
+            "uint" | "uint32" => Type::UInt,
+            "float" | "float32" => Type::Float,
+            "double" | "float64" => Type::Double,
+            _ => bail!("unknown property type {}", name),
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Type::Char => "char",
+            Type::UChar => "uchar",
+            Type::Short => "short",
+            Type::UShort => "ushort",
+            Type::Int => "int",
+            Type::UInt => "uint",
+            Type::Float => "float",
+            Type::Double => "double",
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Type::Char | Type::UChar => 1,
+            Type::Short | Type::UShort => 2,
+            Type::Int | Type::UInt | Type::Float => 4,
+            Type::Double => 8,
+        }
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self, Type::Float | Type::Double)
+    }
+
+    fn decode(&self, b: &[u8]) -> f64 {
+        match self {
+            Type::Char => i8::from_le_bytes([b[0]]) as f64,
+            Type::UChar => b[0] as f64,
+            Type::Short => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Type::UShort => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Type::Int => i32::from_le_bytes(b.try_into().unwrap()) as f64,
+            Type::UInt => u32::from_le_bytes(b.try_into().unwrap()) as f64,
+            Type::Float => f32::from_le_bytes(b.try_into().unwrap()) as f64,
+            Type::Double => f64::from_le_bytes(b.try_into().unwrap()),
+        }
+    }
+
+    fn encode(self, value: f64) -> Vec<u8> {
+        match self {
+            Type::Char => (value as i8).to_le_bytes().to_vec(),
+            Type::UChar => (value as u8).to_le_bytes().to_vec(),
+            Type::Short => (value as i16).to_le_bytes().to_vec(),
+            Type::UShort => (value as u16).to_le_bytes().to_vec(),
+            Type::Int => (value as i32).to_le_bytes().to_vec(),
+            Type::UInt => (value as u32).to_le_bytes().to_vec(),
+            Type::Float => (value as f32).to_le_bytes().to_vec(),
+            Type::Double => value.to_le_bytes().to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Property {
+    Scalar {
+        name: String,
+        kind: Type,
+    },
+    List {
+        name: String,
+        count: Type,
+        item: Type,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Reads the values following the header, either as whitespace separated
+/// words or as little endian binary numbers.
+enum Body<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary(&'a [u8]),
+}
+
+impl Body<'_> {
+    fn value(&mut self, kind: Type) -> Result<f64> {
+        match self {
+            Body::Ascii(words) => {
+                let word = words.next().context("unexpected end of file")?;
+                word.parse()
+                    .context(format!("invalid {} value {}", kind.name(), word))
+            }
+            Body::Binary(data) => {
+                ensure!(data.len() >= kind.size(), "unexpected end of file");
+                let (bytes, rest) = data.split_at(kind.size());
+                *data = rest;
+                Ok(kind.decode(bytes))
+            }
+        }
+    }
+
+    /// Values of all properties of one element; lists are flattened.
+    fn row(&mut self, properties: &[Property]) -> Result<Vec<Vec<f64>>> {
+        properties
+            .iter()
+            .map(|property| match property {
+                Property::Scalar { kind, .. } => Ok(vec![self.value(*kind)?]),
+                Property::List { count, item, .. } => {
+                    let n = self.value(*count)? as usize;
+                    (0..n).map(|_| self.value(*item)).collect()
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn read_ply(file_name: &str) -> Result<Mesh> {
+    let data = fs::read(file_name).context(format!("unable to read file {}", file_name))?;
+    parse_ply(&data).context(format!("something went wrong reading file {}", file_name))
+}
+
+pub fn parse_ply(data: &[u8]) -> Result<Mesh> {
+    let end = b"end_header";
+    let header_end = data
+        .windows(end.len())
+        .position(|w| w == end)
+        .context("missing end_header")?;
+    let body_start = data[header_end..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |i| header_end + i + 1);
+    let header = std::str::from_utf8(&data[..header_end]).context("header is not valid UTF-8")?;
+
+    let mut lines = header.lines();
+    ensure!(lines.next().map(str::trim) == Some("ply"), "not a PLY file");
+
+    let mut binary = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", "ascii", _] => binary = Some(false),
+            ["format", "binary_little_endian", _] => binary = Some(true),
+            ["format", format, _] => bail!("unsupported PLY format {}", format),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .context(format!("invalid count in {}", line))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .context("property before first element")?
+                .properties
+                .push(Property::List {
+                    name: name.to_string(),
+                    count: Type::from_name(count)?,
+                    item: Type::from_name(item)?,
+                }),
+            ["property", kind, name] => elements
+                .last_mut()
+                .context("property before first element")?
+                .properties
+                .push(Property::Scalar {
+                    name: name.to_string(),
+                    kind: Type::from_name(kind)?,
+                }),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => bail!("unexpected header line {}", line),
+        }
+    }
+
+    let body = &data[body_start..];
+    let mut body = match binary.context("missing format line")? {
+        true => Body::Binary(body),
+        false => Body::Ascii(
+            std::str::from_utf8(body)
+                .context("ASCII body is not valid UTF-8")?
+                .split_ascii_whitespace(),
+        ),
+    };
+
+    let mut mesh = Mesh::default();
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut body, element, &mut mesh)?,
+            "face" => read_faces(&mut body, element, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    body.row(&element.properties)?;
+                }
+            }
+        }
+    }
+
+    let n = mesh.vertices.len();
+    for &(a, b, c) in &mesh.triangles {
+        ensure!(a < n && b < n && c < n, "face refers to a missing vertex");
+    }
+
+    Ok(mesh)
+}
+
+fn read_vertices(body: &mut Body, element: &Element, mesh: &mut Mesh) -> Result<()> {
+    let position = |name: &str| {
+        element.properties.iter().position(|p| match p {
+            Property::Scalar { name: n, .. } => n == name,
+            Property::List { .. } => false,
+        })
+    };
+    let find_all = |names: [&str; 3]| -> Option<[usize; 3]> {
+        let [a, b, c] = names.map(position);
+        Some([a?, b?, c?])
+    };
+
+    let coordinates = find_all(["x", "y", "z"]).context("vertices need x, y and z")?;
+    let normals = find_all(["nx", "ny", "nz"]);
+    let colors = find_all(["red", "green", "blue"]);
+    let scalars: Vec<usize> = (0..element.properties.len())
+        .filter(|i| {
+            let used = |columns: Option<[usize; 3]>| columns.is_some_and(|c| c.contains(i));
+            !used(Some(coordinates))
+                && !used(normals)
+                && !used(colors)
+                && matches!(element.properties[*i], Property::Scalar { .. })
+        })
+        .collect();
+    // colors given as floats are between 0 and 1
+    let color_scale = match colors.map(|c| &element.properties[c[0]]) {
+        Some(Property::Scalar { kind, .. }) if kind.is_float() => 255.0,
+        _ => 1.0,
+    };
+
+    mesh.normals = normals.map(|_| Vec::with_capacity(element.count));
+    mesh.colors = colors.map(|_| Vec::with_capacity(element.count));
+    mesh.vertex_scalars = scalars
+        .iter()
+        .map(|i| match &element.properties[*i] {
+            Property::Scalar { name, .. } => (name.clone(), Vec::with_capacity(element.count)),
+            Property::List { .. } => unreachable!(),
+        })
+        .collect();
+
+    let vector = |row: &[Vec<f64>], [a, b, c]: [usize; 3]| Vector3 {
+        x: row[a][0],
+        y: row[b][0],
+        z: row[c][0],
+    };
+    for _ in 0..element.count {
+        let row = body.row(&element.properties)?;
+        mesh.vertices.push(vector(&row, coordinates));
+        if let (Some(normals), Some(columns)) = (&mut mesh.normals, normals) {
+            normals.push(vector(&row, columns));
+        }
+        if let (Some(colors), Some(columns)) = (&mut mesh.colors, colors) {
+            colors.push(columns.map(|i| (row[i][0] * color_scale).round().clamp(0.0, 255.0) as u8));
+        }
+        for ((_, values), i) in mesh.vertex_scalars.iter_mut().zip(&scalars) {
+            values.push(row[*i][0]);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_faces(body: &mut Body, element: &Element, mesh: &mut Mesh) -> Result<()> {
+    let column = element
+        .properties
+        .iter()
+        .position(|p| {
+            matches!(p, Property::List { name, .. }
+                if name == "vertex_indices" || name == "vertex_index")
+        })
+        .context("faces need a vertex_indices list")?;
+
+    for _ in 0..element.count {
+        let row = body.row(&element.properties)?;
+        let face = &row[column];
+        ensure!(face.len() >= 3, "face with fewer than 3 vertices");
+        ensure!(
+            face.iter().all(|&i| i >= 0.0),
+            "face with a negative vertex index"
+        );
+        // polygons are split into a fan of triangles
+        for i in 1..face.len() - 1 {
+            mesh.triangles
+                .push((face[0] as usize, face[i] as usize, face[i + 1] as usize));
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes coordinates, normals and vertex scalars as doubles, colors as
+/// bytes. Triangle tags and triangle scalars are left out.
+pub fn write_ply(file_name: &str, mesh: &Mesh, binary: bool) -> Result<()> {
+    let error_message = format!("unable to write file {}", file_name);
+    let mut f = BufWriter::new(fs::File::create(file_name).context(error_message.clone())?);
+    write_contents(&mut f, mesh, binary)
+        .and_then(|_| f.flush())
+        .context(error_message)
+}
+
+fn write_contents(f: &mut impl Write, mesh: &Mesh, binary: bool) -> std::io::Result<()> {
+    writeln!(f, "ply")?;
+    if binary {
+        writeln!(f, "format binary_little_endian 1.0")?;
+    } else {
+        writeln!(f, "format ascii 1.0")?;
+    }
+    writeln!(f, "element vertex {}", mesh.vertices.len())?;
+    for name in ["x", "y", "z"] {
+        writeln!(f, "property double {}", name)?;
+    }
+    if mesh.normals.is_some() {
+        for name in ["nx", "ny", "nz"] {
+            writeln!(f, "property double {}", name)?;
+        }
+    }
+    if mesh.colors.is_some() {
+        for name in ["red", "green", "blue"] {
+            writeln!(f, "property uchar {}", name)?;
+        }
+    }
+    for (name, _) in &mesh.vertex_scalars {
+        // names are single words in the header
+        writeln!(
+            f,
+            "property double {}",
+            name.replace(char::is_whitespace, "_")
+        )?;
+    }
+    writeln!(f, "element face {}", mesh.triangles.len())?;
+    writeln!(f, "property list uchar int vertex_indices")?;
+    writeln!(f, "end_header")?;
+
+    let mut write_row = |row: &[(Type, f64)]| -> std::io::Result<()> {
+        if binary {
+            for (kind, value) in row {
+                f.write_all(&kind.encode(*value))?;
+            }
+            Ok(())
+        } else {
+            let words: Vec<String> = row.iter().map(|(_, value)| value.to_string()).collect();
+            writeln!(f, "{}", words.join(" "))
+        }
+    };
+
+    for (i, p) in mesh.vertices.iter().enumerate() {
+        let mut row = vec![
+            (Type::Double, p.x),
+            (Type::Double, p.y),
+            (Type::Double, p.z),
+        ];
+        if let Some(normals) = &mesh.normals {
+            let n = normals[i];
+            row.extend([
+                (Type::Double, n.x),
+                (Type::Double, n.y),
+                (Type::Double, n.z),
+            ]);
+        }
+        if let Some(colors) = &mesh.colors {
+            row.extend(colors[i].map(|c| (Type::UChar, c as f64)));
+        }
+        for (_, values) in &mesh.vertex_scalars {
+            row.push((Type::Double, values[i]));
+        }
+        write_row(&row)?;
+    }
+
+    for &(a, b, c) in &mesh.triangles {
+        write_row(&[
+            (Type::UChar, 3.0),
+            (Type::Int, a as f64),
+            (Type::Int, b as f64),
+            (Type::Int, c as f64),
+        ])?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ply() {
+        let data = b"ply
+format ascii 1.0
+comment quad with float colors and an extra element
+element vertex 4
+property float x
+property float y
+property float z
+property float red
+property float green
+property float blue
+property float quality
+element face 1
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+0 0 0 1 0 0 0.5
+1 0 0 0 1 0 1.5
+1 1 0 0 0 1 2.5
+0 1 0 1 1 1 3.5
+4 0 1 2 3
+0 2
+";
+        let mesh = parse_ply(data).unwrap();
+
+        assert_eq!(mesh.triangles, vec![(0, 1, 2), (0, 2, 3)]);
+        assert_eq!(mesh.colors.unwrap()[1], [0, 255, 0]);
+        assert_eq!(mesh.normals, None);
+        assert_eq!(
+            mesh.vertex_scalars,
+            vec![("quality".to_string(), vec![0.5, 1.5, 2.5, 3.5])]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let point = |x, y, z| Vector3 { x, y, z };
+        let mut mesh = Mesh::new(
+            vec![
+                point(0.0, 0.0, 0.0),
+                point(1.5, 0.0, 0.0),
+                point(0.0, 0.1, -2.0),
+            ],
+            vec![(0, 1, 2), (2, 1, 0)],
+        );
+        mesh.normals = Some(vec![point(0.0, 0.6, 0.8); 3]);
+        mesh.colors = Some(vec![[0, 128, 255], [1, 2, 3], [255, 255, 255]]);
+        mesh.vertex_scalars = vec![("thickness".to_string(), vec![0.1, 2.0, -3.0])];
+
+        for binary in [false, true] {
+            let file_name = std::env::temp_dir().join(format!("mesh-ply-{}.ply", binary));
+            let file_name = file_name.to_str().unwrap();
+            write_ply(file_name, &mesh, binary).unwrap();
+            let read_back = read_ply(file_name).unwrap();
+            fs::remove_file(file_name).unwrap();
+
+            assert_eq!(read_back, mesh);
+        }
+    }
+}
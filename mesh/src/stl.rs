@@ -0,0 +1,241 @@
+//! Reading and writing of STL files, both ASCII and binary. STL stores every
+//! triangle with its own three corners, so vertices with identical
+//! coordinates are welded into one on import.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+
+use crate::mesh::Mesh;
+use crate::vector::{vec_cross_vec, vec_minus_vec, Vector3};
+
+const HEADER_SIZE: usize = 80;
+const TRIANGLE_SIZE: usize = 50;
+
+pub fn read_stl(file_name: &str) -> Result<Mesh> {
+    let data = fs::read(file_name).context(format!("unable to read file {}", file_name))?;
+    parse_stl(&data).context(format!("something went wrong reading file {}", file_name))
+}
+
+/// Parses ASCII or binary STL. Binary files may also start with "solid", so
+/// a file is taken as binary whenever its size matches the triangle count
+/// in the header.
+pub fn parse_stl(data: &[u8]) -> Result<Mesh> {
+    if data.len() >= HEADER_SIZE + 4 {
+        let count = u32::from_le_bytes(data[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap());
+        if data.len() == HEADER_SIZE + 4 + TRIANGLE_SIZE * count as usize {
+            return parse_binary(&data[HEADER_SIZE + 4..]);
+        }
+    }
+
+    ensure!(
+        data.trim_ascii_start().starts_with(b"solid"),
+        "neither an ASCII nor a binary STL file"
+    );
+    parse_ascii(std::str::from_utf8(data).context("ASCII STL file is not valid UTF-8")?)
+}
+
+fn parse_binary(data: &[u8]) -> Result<Mesh> {
+    let float =
+        |bytes: &[u8], i: usize| f32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap());
+
+    let mut corners = Vec::with_capacity(3 * data.len() / TRIANGLE_SIZE);
+    for facet in data.chunks_exact(TRIANGLE_SIZE) {
+        // skip the normal, which is recomputed from the corners when needed
+        for corner in 1..4 {
+            corners.push(Vector3 {
+                x: float(facet, 3 * corner) as f64,
+                y: float(facet, 3 * corner + 1) as f64,
+                z: float(facet, 3 * corner + 2) as f64,
+            });
+        }
+    }
+
+    Ok(weld_vertices(&corners))
+}
+
+fn parse_ascii(contents: &str) -> Result<Mesh> {
+    let mut corners = Vec::new();
+    let mut facet_corners = 0;
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            Some(&"facet") => facet_corners = 0,
+            Some(&"vertex") => {
+                ensure!(
+                    words.len() == 4,
+                    "expected three coordinates on line {}",
+                    line_number + 1
+                );
+                let [x, y, z] = [1, 2, 3].map(|i| words[i].parse::<f64>());
+                corners.push(Vector3 {
+                    x: x.context(format!("invalid coordinate on line {}", line_number + 1))?,
+                    y: y.context(format!("invalid coordinate on line {}", line_number + 1))?,
+                    z: z.context(format!("invalid coordinate on line {}", line_number + 1))?,
+                });
+                facet_corners += 1;
+            }
+            Some(&"endfacet") => ensure!(
+                facet_corners == 3,
+                "facet ending on line {} has {} vertices instead of 3",
+                line_number + 1,
+                facet_corners
+            ),
+            _ => {}
+        }
+    }
+    ensure!(corners.len() % 3 == 0, "unterminated facet at end of file");
+
+    Ok(weld_vertices(&corners))
+}
+
+/// Builds a mesh from triangle corners given three at a time, merging
+/// corners with exactly the same coordinates. Vertices are numbered in order
+/// of first appearance.
+pub fn weld_vertices(corners: &[Vector3]) -> Mesh {
+    // -0.0 and 0.0 compare equal but have different bits
+    let key = |p: &Vector3| [p.x, p.y, p.z].map(|c| (c + 0.0).to_bits());
+
+    let mut indices: HashMap<[u64; 3], usize> = HashMap::new();
+    let mut vertices = Vec::new();
+    let mut welded = Vec::with_capacity(corners.len());
+    for corner in corners {
+        let index = *indices.entry(key(corner)).or_insert_with(|| {
+            vertices.push(*corner);
+            vertices.len() - 1
+        });
+        welded.push(index);
+    }
+
+    let triangles = welded.chunks_exact(3).map(|t| (t[0], t[1], t[2])).collect();
+    Mesh::new(vertices, triangles)
+}
+
+/// Unit normal of each triangle from its winding, zero for degenerate ones.
+fn normals(mesh: &Mesh) -> Vec<Vector3> {
+    mesh.triangles
+        .iter()
+        .map(|&(a, b, c)| {
+            let n = vec_cross_vec(
+                &vec_minus_vec(&mesh.vertices[b], &mesh.vertices[a]),
+                &vec_minus_vec(&mesh.vertices[c], &mesh.vertices[a]),
+            );
+            let length = (n.x * n.x + n.y * n.y + n.z * n.z).sqrt();
+            if length > 0.0 {
+                Vector3 {
+                    x: n.x / length,
+                    y: n.y / length,
+                    z: n.z / length,
+                }
+            } else {
+                n
+            }
+        })
+        .collect()
+}
+
+pub fn write_stl(file_name: &str, mesh: &Mesh, binary: bool) -> Result<()> {
+    let error_message = format!("unable to write file {}", file_name);
+    let mut f = BufWriter::new(fs::File::create(file_name).context(error_message.clone())?);
+    if binary {
+        write_binary(&mut f, mesh)
+    } else {
+        write_ascii(&mut f, mesh)
+    }
+    .and_then(|_| f.flush())
+    .context(error_message)
+}
+
+fn write_ascii(f: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+    writeln!(f, "solid mesh")?;
+    for (n, &(a, b, c)) in normals(mesh).iter().zip(&mesh.triangles) {
+        writeln!(f, "facet normal {} {} {}", n.x, n.y, n.z)?;
+        writeln!(f, "  outer loop")?;
+        for i in [a, b, c] {
+            let p = &mesh.vertices[i];
+            writeln!(f, "    vertex {} {} {}", p.x, p.y, p.z)?;
+        }
+        writeln!(f, "  endloop")?;
+        writeln!(f, "endfacet")?;
+    }
+    writeln!(f, "endsolid mesh")
+}
+
+/// Coordinates are stored as 32 bit floats, so some precision is lost.
+fn write_binary(f: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+    // the header must not start with "solid"
+    let mut header = [b' '; HEADER_SIZE];
+    let title = b"binary STL";
+    header[..title.len()].copy_from_slice(title);
+    f.write_all(&header)?;
+    f.write_all(&(mesh.triangles.len() as u32).to_le_bytes())?;
+
+    for (n, &(a, b, c)) in normals(mesh).iter().zip(&mesh.triangles) {
+        for p in [n, &mesh.vertices[a], &mesh.vertices[b], &mesh.vertices[c]] {
+            for value in [p.x, p.y, p.z] {
+                f.write_all(&(value as f32).to_le_bytes())?;
+            }
+        }
+        f.write_all(&0u16.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tetrahedron() -> Mesh {
+        let point = |x, y, z| Vector3 { x, y, z };
+        Mesh::new(
+            vec![
+                point(0.0, 0.0, 0.0),
+                point(1.0, 0.0, 0.0),
+                point(0.0, 1.5, 0.0),
+                point(0.0, 0.0, -2.25),
+            ],
+            vec![(0, 1, 2), (0, 3, 1), (1, 3, 2), (0, 2, 3)],
+        )
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for binary in [false, true] {
+            let file_name = std::env::temp_dir().join(format!("mesh-stl-{}.stl", binary));
+            let file_name = file_name.to_str().unwrap();
+            write_stl(file_name, &tetrahedron(), binary).unwrap();
+            let read_back = read_stl(file_name).unwrap();
+            fs::remove_file(file_name).unwrap();
+
+            assert_eq!(read_back, tetrahedron());
+        }
+    }
+
+    #[test]
+    fn test_ascii_welding() {
+        let data = b"solid test
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 0 1 0
+  endloop
+endfacet
+facet normal 0 0 1
+  outer loop
+    vertex 1 0 0
+    vertex 1 1 0
+    vertex -0 1 0
+  endloop
+endfacet
+endsolid test
+";
+        let mesh = parse_stl(data).unwrap();
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.triangles, vec![(0, 1, 2), (1, 3, 2)]);
+
+        assert!(parse_stl(b"solid test\nfacet normal 0 0 1\nvertex 0 0 0\nendfacet\n").is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! The plain text format: number of points, one `x y z` line per point,
+//! number of triangles, one `i j k` line per triangle with 0-based indices,
+//! or 1-based ones as written by Gmsh.
+//!
+//! The file is read line by line. Problems are reported as a [`ParseError`]
+//! with the line number and what was wrong there.
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::str::FromStr;
+
+use crate::mesh::Mesh;
+use crate::vector::Vector3;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The file ended before all announced points or triangles were read.
+    CountMismatch {
+        what: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    /// A line has the wrong number of values.
+    WrongArity { expected: usize, found: usize },
+    /// A value is not a number, or not a non-negative integer where a count
+    /// or index is expected.
+    InvalidNumber { token: String },
+    /// A triangle refers to a point that does not exist. `first_index` is
+    /// 0 or 1 depending on how points are counted.
+    IndexOutOfRange {
+        index: usize,
+        first_index: usize,
+        num_points: usize,
+    },
+    /// Something other than whitespace follows the last triangle.
+    TrailingData { token: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line number
+    pub line: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ParseErrorKind::CountMismatch {
+                what,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected {} {} but the file ends after {}",
+                expected, what, found
+            ),
+            ParseErrorKind::WrongArity { expected, found } => {
+                write!(f, "expected {} values but found {}", expected, found)
+            }
+            ParseErrorKind::InvalidNumber { token } => {
+                write!(f, "could not parse \"{}\" as a number", token)
+            }
+            ParseErrorKind::IndexOutOfRange {
+                index,
+                first_index,
+                num_points,
+            } => match num_points {
+                0 => write!(f, "index {} refers to a point but there are none", index),
+                _ => write!(
+                    f,
+                    "index {} is out of range, expected {} to {}",
+                    index,
+                    first_index,
+                    first_index + num_points - 1
+                ),
+            },
+            ParseErrorKind::TrailingData { token } => {
+                write!(f, "unexpected \"{}\" after the last triangle", token)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+struct Lines<R> {
+    lines: std::io::Lines<R>,
+    line_number: usize,
+}
+
+impl<R: BufRead> Lines<R> {
+    fn next(&mut self) -> Result<Option<String>> {
+        match self.lines.next() {
+            Some(line) => {
+                self.line_number += 1;
+                Ok(Some(line?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> anyhow::Error {
+        ParseError {
+            line: self.line_number,
+            kind,
+        }
+        .into()
+    }
+
+    /// Values of the next line, which must hold exactly `N` of them.
+    fn values<T: FromStr, const N: usize>(
+        &mut self,
+        what: &'static str,
+        expected: usize,
+        found: usize,
+    ) -> Result<[T; N]> {
+        let Some(line) = self.next()? else {
+            self.line_number += 1;
+            return Err(self.error(ParseErrorKind::CountMismatch {
+                what,
+                expected,
+                found,
+            }));
+        };
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() != N {
+            return Err(self.error(ParseErrorKind::WrongArity {
+                expected: N,
+                found: words.len(),
+            }));
+        }
+        let mut values = Vec::with_capacity(N);
+        for word in words {
+            let value = word.parse().map_err(|_| {
+                self.error(ParseErrorKind::InvalidNumber {
+                    token: word.to_string(),
+                })
+            })?;
+            values.push(value);
+        }
+        Ok(values.try_into().ok().unwrap())
+    }
+}
+
+/// Reads a text file whose triangle indices start at `first_index`, 0 or 1.
+pub fn read_text(file_name: &str, first_index: usize) -> Result<Mesh> {
+    let file = fs::File::open(file_name).context(format!("unable to read file {}", file_name))?;
+    parse_text(BufReader::new(file), first_index)
+        .context(format!("something went wrong reading file {}", file_name))
+}
+
+/// Like [`read_text`], but keeps triangles which refer to points that do not
+/// exist, e.g. to report them. Indices below `first_index` become
+/// `usize::MAX`.
+pub fn read_text_unchecked(file_name: &str, first_index: usize) -> Result<Mesh> {
+    let file = fs::File::open(file_name).context(format!("unable to read file {}", file_name))?;
+    parse(BufReader::new(file), first_index, false)
+        .context(format!("something went wrong reading file {}", file_name))
+}
+
+pub fn parse_text<R: BufRead>(reader: R, first_index: usize) -> Result<Mesh> {
+    parse(reader, first_index, true)
+}
+
+fn parse<R: BufRead>(reader: R, first_index: usize, check_indices: bool) -> Result<Mesh> {
+    let mut lines = Lines {
+        lines: reader.lines(),
+        line_number: 0,
+    };
+
+    let [num_points] = lines.values::<usize, 1>("point count", 1, 0)?;
+    let mut points = Vec::new();
+    for i in 0..num_points {
+        let [x, y, z] = lines.values("points", num_points, i)?;
+        points.push(Vector3 { x, y, z });
+    }
+
+    let [num_triangles] = lines.values::<usize, 1>("triangle count", 1, 0)?;
+    let mut triangles = Vec::new();
+    for i in 0..num_triangles {
+        let [a, b, c] = lines.values::<usize, 3>("triangles", num_triangles, i)?;
+        let in_range = |index| index >= first_index && index - first_index < num_points;
+        let out_of_range = [a, b, c].into_iter().find(|&index| !in_range(index));
+        if let (true, Some(index)) = (check_indices, out_of_range) {
+            return Err(lines.error(ParseErrorKind::IndexOutOfRange {
+                index,
+                first_index,
+                num_points,
+            }));
+        }
+        let [a, b, c] = [a, b, c].map(|index| index.checked_sub(first_index).unwrap_or(usize::MAX));
+        triangles.push((a, b, c));
+    }
+
+    while let Some(line) = lines.next()? {
+        if let Some(token) = line.split_whitespace().next() {
+            return Err(lines.error(ParseErrorKind::TrailingData {
+                token: token.to_string(),
+            }));
+        }
+    }
+
+    Ok(Mesh::new(points, triangles))
+}
+
+pub fn write_text(file_name: &str, mesh: &Mesh) -> Result<()> {
+    let error_message = format!("unable to write file {}", file_name);
+    let mut f = BufWriter::new(fs::File::create(file_name).context(error_message.clone())?);
+    write_contents(&mut f, mesh)
+        .and_then(|_| f.flush())
+        .context(error_message)
+}
+
+fn write_contents(f: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+    // write points
+    writeln!(f, "{}", mesh.vertices.len())?;
+    for point in &mesh.vertices {
+        writeln!(f, "{} {} {}", point.x, point.y, point.z)?;
+    }
+
+    // write triangles
+    writeln!(f, "{}", mesh.triangles.len())?;
+    for (i, j, k) in &mesh.triangles {
+        writeln!(f, "{} {} {}", i, j, k)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(contents: &str) -> ParseError {
+        parse_text(contents.as_bytes(), 0)
+            .unwrap_err()
+            .downcast()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_errors() {
+        let kind = |contents| error(contents).kind;
+
+        assert_eq!(
+            error("3\n0 0 0\n1 0 0\n"),
+            ParseError {
+                line: 4,
+                kind: ParseErrorKind::CountMismatch {
+                    what: "points",
+                    expected: 3,
+                    found: 2
+                }
+            }
+        );
+        assert_eq!(
+            error("2\n0 0 0\n1 0\n"),
+            ParseError {
+                line: 3,
+                kind: ParseErrorKind::WrongArity {
+                    expected: 3,
+                    found: 2
+                }
+            }
+        );
+        assert_eq!(
+            kind("1\n0 0 zero\n"),
+            ParseErrorKind::InvalidNumber {
+                token: "zero".to_string()
+            }
+        );
+        assert_eq!(
+            kind("3\n0 0 0\n1 0 0\n0 1 0\n1\n0 1 -2\n"),
+            ParseErrorKind::InvalidNumber {
+                token: "-2".to_string()
+            }
+        );
+        assert_eq!(
+            error("3\n0 0 0\n1 0 0\n0 1 0\n1\n0 1 3\n"),
+            ParseError {
+                line: 6,
+                kind: ParseErrorKind::IndexOutOfRange {
+                    index: 3,
+                    first_index: 0,
+                    num_points: 3
+                }
+            }
+        );
+        assert_eq!(
+            error("0\n0\n\nagain\n"),
+            ParseError {
+                line: 4,
+                kind: ParseErrorKind::TrailingData {
+                    token: "again".to_string()
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_trailing_whitespace() {
+        let mesh = parse_text("3\n0 0 0\n1 0 0\n0 1 0\n1\n0 1 2\n\n  \n".as_bytes(), 0).unwrap();
+        assert_eq!(mesh.triangles, vec![(0, 1, 2)]);
+    }
+
+    #[test]
+    fn test_one_based() {
+        let contents = "3\n0 0 0\n1 0 0\n0 1 0\n2\n1 2 3\n3 2 1\n";
+        let mesh = parse_text(contents.as_bytes(), 1).unwrap();
+        assert_eq!(mesh.triangles, vec![(0, 1, 2), (2, 1, 0)]);
+
+        let contents = "3\n0 0 0\n1 0 0\n0 1 0\n1\n0 1 2\n";
+        let error: ParseError = parse_text(contents.as_bytes(), 1)
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "line 6: index 0 is out of range, expected 1 to 3"
+        );
+
+        let mesh = parse(contents.as_bytes(), 1, false).unwrap();
+        assert_eq!(mesh.triangles, vec![(usize::MAX, 0, 1)]);
+    }
+}
@@ -0,0 +1,19 @@
+use crate::mesh::Triplet;
+use crate::vector::Vector3;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Triangle {
+    pub vertex1: Vector3,
+    pub vertex2: Vector3,
+    pub vertex3: Vector3,
+}
+
+impl Triangle {
+    pub fn from_indices(coordinates: &[Vector3], (a, b, c): Triplet) -> Self {
+        Triangle {
+            vertex1: coordinates[a],
+            vertex2: coordinates[b],
+            vertex3: coordinates[c],
+        }
+    }
+}
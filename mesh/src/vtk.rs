@@ -0,0 +1,240 @@
+//! Writing of VTK PolyData for ParaView, as legacy `.vtk` or XML `.vtp`
+//! files. Normals, colors and vertex scalars become point data, tags and
+//! triangle scalars cell data.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{BufWriter, Write};
+
+use crate::mesh::Mesh;
+
+pub fn write_vtk(file_name: &str, mesh: &Mesh) -> Result<()> {
+    write_file(file_name, |f| write_legacy(f, mesh))
+}
+
+pub fn write_vtp(file_name: &str, mesh: &Mesh) -> Result<()> {
+    write_file(file_name, |f| write_xml(f, mesh))
+}
+
+fn write_file<F>(file_name: &str, write: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<fs::File>) -> std::io::Result<()>,
+{
+    let error_message = format!("unable to write file {}", file_name);
+    let mut f = BufWriter::new(fs::File::create(file_name).context(error_message.clone())?);
+    write(&mut f).and_then(|_| f.flush()).context(error_message)
+}
+
+/// Array names are single words in legacy files.
+fn legacy_name(name: &str) -> String {
+    name.replace(char::is_whitespace, "_")
+}
+
+fn write_legacy(f: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+    writeln!(f, "# vtk DataFile Version 3.0")?;
+    writeln!(f, "mesh")?;
+    writeln!(f, "ASCII")?;
+    writeln!(f, "DATASET POLYDATA")?;
+
+    writeln!(f, "POINTS {} double", mesh.vertices.len())?;
+    for p in &mesh.vertices {
+        writeln!(f, "{} {} {}", p.x, p.y, p.z)?;
+    }
+    writeln!(
+        f,
+        "POLYGONS {} {}",
+        mesh.triangles.len(),
+        4 * mesh.triangles.len()
+    )?;
+    for (a, b, c) in &mesh.triangles {
+        writeln!(f, "3 {} {} {}", a, b, c)?;
+    }
+
+    if mesh.tags.is_some() || !mesh.triangle_scalars.is_empty() {
+        writeln!(f, "CELL_DATA {}", mesh.triangles.len())?;
+        if let Some(tags) = &mesh.tags {
+            writeln!(f, "SCALARS tag int 1")?;
+            writeln!(f, "LOOKUP_TABLE default")?;
+            for tag in tags {
+                writeln!(f, "{}", tag)?;
+            }
+        }
+        for (name, values) in &mesh.triangle_scalars {
+            writeln!(f, "SCALARS {} double 1", legacy_name(name))?;
+            writeln!(f, "LOOKUP_TABLE default")?;
+            for value in values {
+                writeln!(f, "{}", value)?;
+            }
+        }
+    }
+
+    if mesh.normals.is_some() || mesh.colors.is_some() || !mesh.vertex_scalars.is_empty() {
+        writeln!(f, "POINT_DATA {}", mesh.vertices.len())?;
+        if let Some(normals) = &mesh.normals {
+            writeln!(f, "NORMALS normals double")?;
+            for n in normals {
+                writeln!(f, "{} {} {}", n.x, n.y, n.z)?;
+            }
+        }
+        if let Some(colors) = &mesh.colors {
+            writeln!(f, "COLOR_SCALARS colors 3")?;
+            for color in colors {
+                let [r, g, b] = color.map(|c| c as f64 / 255.0);
+                writeln!(f, "{} {} {}", r, g, b)?;
+            }
+        }
+        for (name, values) in &mesh.vertex_scalars {
+            writeln!(f, "SCALARS {} double 1", legacy_name(name))?;
+            writeln!(f, "LOOKUP_TABLE default")?;
+            for value in values {
+                writeln!(f, "{}", value)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn escape(name: &str) -> String {
+    name.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes one inline ASCII data array.
+fn data_array<T: std::fmt::Display>(
+    f: &mut impl Write,
+    kind: &str,
+    name: &str,
+    components: usize,
+    values: impl Iterator<Item = T>,
+) -> std::io::Result<()> {
+    writeln!(
+        f,
+        "        <DataArray type=\"{}\" Name=\"{}\" NumberOfComponents=\"{}\" format=\"ascii\">",
+        kind,
+        escape(name),
+        components
+    )?;
+    for value in values {
+        write!(f, "{} ", value)?;
+    }
+    writeln!(f)?;
+    writeln!(f, "        </DataArray>")
+}
+
+fn write_xml(f: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+    writeln!(f, "<?xml version=\"1.0\"?>")?;
+    writeln!(
+        f,
+        "<VTKFile type=\"PolyData\" version=\"0.1\" byte_order=\"LittleEndian\">"
+    )?;
+    writeln!(f, "  <PolyData>")?;
+    writeln!(
+        f,
+        "    <Piece NumberOfPoints=\"{}\" NumberOfVerts=\"0\" NumberOfLines=\"0\" NumberOfStrips=\"0\" NumberOfPolys=\"{}\">",
+        mesh.vertices.len(),
+        mesh.triangles.len()
+    )?;
+
+    if mesh.normals.is_some() {
+        writeln!(f, "      <PointData Normals=\"normals\">")?;
+    } else {
+        writeln!(f, "      <PointData>")?;
+    }
+    if let Some(normals) = &mesh.normals {
+        let values = normals.iter().flat_map(|n| [n.x, n.y, n.z]);
+        data_array(f, "Float64", "normals", 3, values)?;
+    }
+    if let Some(colors) = &mesh.colors {
+        data_array(f, "UInt8", "colors", 3, colors.iter().flatten())?;
+    }
+    for (name, values) in &mesh.vertex_scalars {
+        data_array(f, "Float64", name, 1, values.iter())?;
+    }
+    writeln!(f, "      </PointData>")?;
+
+    writeln!(f, "      <CellData>")?;
+    if let Some(tags) = &mesh.tags {
+        data_array(f, "Int32", "tag", 1, tags.iter())?;
+    }
+    for (name, values) in &mesh.triangle_scalars {
+        data_array(f, "Float64", name, 1, values.iter())?;
+    }
+    writeln!(f, "      </CellData>")?;
+
+    writeln!(f, "      <Points>")?;
+    let points = mesh.vertices.iter().flat_map(|p| [p.x, p.y, p.z]);
+    data_array(f, "Float64", "points", 3, points)?;
+    writeln!(f, "      </Points>")?;
+
+    writeln!(f, "      <Polys>")?;
+    let connectivity = mesh.triangles.iter().flat_map(|&(a, b, c)| [a, b, c]);
+    data_array(f, "Int64", "connectivity", 1, connectivity)?;
+    let offsets = (1..=mesh.triangles.len()).map(|i| 3 * i);
+    data_array(f, "Int64", "offsets", 1, offsets)?;
+    writeln!(f, "      </Polys>")?;
+
+    writeln!(f, "    </Piece>")?;
+    writeln!(f, "  </PolyData>")?;
+    writeln!(f, "</VTKFile>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Vector3;
+
+    fn square() -> Mesh {
+        let point = |x, y| Vector3 { x, y, z: 0.0 };
+        Mesh {
+            vertices: vec![
+                point(0.0, 0.0),
+                point(1.0, 0.0),
+                point(1.0, 1.0),
+                point(0.0, 1.0),
+            ],
+            triangles: vec![(0, 1, 2), (0, 2, 3)],
+            tags: Some(vec![1005, 1006]),
+            vertex_scalars: vec![("inside".to_string(), vec![0.0, 1.0, 0.0, 1.0])],
+            triangle_scalars: vec![("drop reason".to_string(), vec![0.0, 2.0])],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_legacy() {
+        let mut data = Vec::new();
+        write_legacy(&mut data, &square()).unwrap();
+        let lines: Vec<&str> = std::str::from_utf8(&data).unwrap().lines().collect();
+
+        assert_eq!(lines[4], "POINTS 4 double");
+        assert_eq!(lines[9..12], ["POLYGONS 2 8", "3 0 1 2", "3 0 2 3"]);
+        assert_eq!(
+            lines[12..16],
+            [
+                "CELL_DATA 2",
+                "SCALARS tag int 1",
+                "LOOKUP_TABLE default",
+                "1005"
+            ]
+        );
+        assert_eq!(lines[17], "SCALARS drop_reason double 1");
+        assert_eq!(lines[21..23], ["POINT_DATA 4", "SCALARS inside double 1"]);
+        assert_eq!(lines.len(), 28);
+    }
+
+    #[test]
+    fn test_xml() {
+        let mut data = Vec::new();
+        write_xml(&mut data, &square()).unwrap();
+        let contents = String::from_utf8(data).unwrap();
+
+        assert!(contents.contains("NumberOfPoints=\"4\""));
+        assert!(contents.contains("Name=\"drop reason\""));
+        assert!(contents.contains("<DataArray type=\"Int32\" Name=\"tag\" NumberOfComponents=\"1\" format=\"ascii\">\n1005 1006 \n"));
+        assert!(contents.contains("0 1 2 0 2 3 \n"));
+        assert!(contents.contains("3 6 \n"));
+    }
+}
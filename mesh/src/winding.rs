@@ -0,0 +1,272 @@
+//! Generalized winding number of a triangle soup, following Jacobson et al.
+//! (2013) with the far-field approximation of Barill et al. (2018): clusters
+//! of triangles far away from the query point are replaced by a dipole.
+
+use crate::bvh::{Bvh, Node};
+use crate::mesh::Triplet;
+use crate::triangle::Triangle;
+use crate::vector::{vec_cross_vec, vec_dot_vec, vec_minus_vec, Vector3};
+
+/// A cluster is approximated if the query point is further away than this
+/// many times the cluster radius.
+const FAR_FIELD_RATIO: f64 = 3.0;
+
+#[derive(Copy, Clone, Debug)]
+struct Dipole {
+    center: Vector3,
+    area_normal: Vector3,
+    radius: f64,
+}
+
+pub struct WindingNumber {
+    bvh: Bvh,
+    triangles: Vec<Triplet>,
+    dipoles: Vec<Dipole>,
+}
+
+impl WindingNumber {
+    pub fn new(coordinates: &[Vector3], triangles: &[Triplet]) -> Self {
+        let bvh = Bvh::new(coordinates, triangles);
+        let dipoles = compute_dipoles(&bvh);
+
+        WindingNumber {
+            bvh,
+            triangles: triangles.to_vec(),
+            dipoles,
+        }
+    }
+
+    /// Winding number at `point`. Triangles which contain the vertex
+    /// `skip_vertex` do not contribute, this is used when evaluating at a
+    /// vertex of the mesh itself.
+    pub fn evaluate(&self, point: &Vector3, skip_vertex: Option<usize>) -> f64 {
+        if self.bvh.nodes.is_empty() {
+            return 0.0;
+        }
+
+        let mut sum = 0.0;
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let dipole = &self.dipoles[node_index];
+            let r = vec_minus_vec(&dipole.center, point);
+            let distance = vec_dot_vec(&r, &r).sqrt();
+            if distance > FAR_FIELD_RATIO * dipole.radius {
+                sum += vec_dot_vec(&r, &dipole.area_normal) / distance.powi(3);
+                continue;
+            }
+
+            match &self.bvh.nodes[node_index] {
+                Node::Leaf { first, count, .. } => {
+                    for (i, triangle) in &self.bvh.triangles[*first..first + count] {
+                        let (a, b, c) = self.triangles[*i];
+                        if skip_vertex.is_some_and(|v| v == a || v == b || v == c) {
+                            continue;
+                        }
+                        sum += solid_angle(triangle, point);
+                    }
+                }
+                Node::Inner { left, right, .. } => {
+                    stack.push(*left);
+                    stack.push(*right);
+                }
+            }
+        }
+
+        sum / (4.0 * std::f64::consts::PI)
+    }
+}
+
+/// Signed solid angle (Van Oosterom and Strackee), positive if `point` is
+/// on the side opposite to the triangle normal.
+fn solid_angle(triangle: &Triangle, point: &Vector3) -> f64 {
+    let a = vec_minus_vec(&triangle.vertex1, point);
+    let b = vec_minus_vec(&triangle.vertex2, point);
+    let c = vec_minus_vec(&triangle.vertex3, point);
+
+    let la = vec_dot_vec(&a, &a).sqrt();
+    let lb = vec_dot_vec(&b, &b).sqrt();
+    let lc = vec_dot_vec(&c, &c).sqrt();
+
+    let numerator = vec_dot_vec(&a, &vec_cross_vec(&b, &c));
+    let denominator = la * lb * lc
+        + vec_dot_vec(&a, &b) * lc
+        + vec_dot_vec(&a, &c) * lb
+        + vec_dot_vec(&b, &c) * la;
+
+    2.0 * numerator.atan2(denominator)
+}
+
+fn area_normal(triangle: &Triangle) -> Vector3 {
+    let edge1 = vec_minus_vec(&triangle.vertex2, &triangle.vertex1);
+    let edge2 = vec_minus_vec(&triangle.vertex3, &triangle.vertex1);
+    let n = vec_cross_vec(&edge1, &edge2);
+    Vector3 {
+        x: 0.5 * n.x,
+        y: 0.5 * n.y,
+        z: 0.5 * n.z,
+    }
+}
+
+/// Area weighted centers and summed area normals of all nodes, children
+/// are combined into their parents.
+fn compute_dipoles(bvh: &Bvh) -> Vec<Dipole> {
+    let zero = Vector3 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    // (weighted center sum, total area, area normal) per node
+    let mut moments = vec![(zero, 0.0, zero); bvh.nodes.len()];
+
+    // children always come after their parent
+    for node_index in (0..bvh.nodes.len()).rev() {
+        let (mut center, mut area, mut normal) = (zero, 0.0, zero);
+        let mut add = |c: Vector3, a: f64, n: Vector3| {
+            center = Vector3 {
+                x: center.x + c.x,
+                y: center.y + c.y,
+                z: center.z + c.z,
+            };
+            area += a;
+            normal = Vector3 {
+                x: normal.x + n.x,
+                y: normal.y + n.y,
+                z: normal.z + n.z,
+            };
+        };
+
+        match &bvh.nodes[node_index] {
+            Node::Leaf { first, count, .. } => {
+                for (_, triangle) in &bvh.triangles[*first..first + count] {
+                    let n = area_normal(triangle);
+                    let a = vec_dot_vec(&n, &n).sqrt();
+                    let c = Vector3 {
+                        x: a * (triangle.vertex1.x + triangle.vertex2.x + triangle.vertex3.x) / 3.0,
+                        y: a * (triangle.vertex1.y + triangle.vertex2.y + triangle.vertex3.y) / 3.0,
+                        z: a * (triangle.vertex1.z + triangle.vertex2.z + triangle.vertex3.z) / 3.0,
+                    };
+                    add(c, a, n);
+                }
+            }
+            Node::Inner { left, right, .. } => {
+                for child in [*left, *right] {
+                    let (c, a, n) = moments[child];
+                    add(c, a, n);
+                }
+            }
+        }
+        moments[node_index] = (center, area, normal);
+    }
+
+    bvh.nodes
+        .iter()
+        .zip(moments)
+        .map(|(node, (weighted_center, area, area_normal))| {
+            let bounds = node.bounds();
+            let center = if area > 0.0 {
+                Vector3 {
+                    x: weighted_center.x / area,
+                    y: weighted_center.y / area,
+                    z: weighted_center.z / area,
+                }
+            } else {
+                Vector3 {
+                    x: 0.5 * (bounds.min.x + bounds.max.x),
+                    y: 0.5 * (bounds.min.y + bounds.max.y),
+                    z: 0.5 * (bounds.min.z + bounds.max.z),
+                }
+            };
+            // distance to the farthest corner of the bounding box
+            let dx = (center.x - bounds.min.x).max(bounds.max.x - center.x);
+            let dy = (center.y - bounds.min.y).max(bounds.max.y - center.y);
+            let dz = (center.z - bounds.min.z).max(bounds.max.z - center.z);
+            Dipole {
+                center,
+                area_normal,
+                radius: (dx * dx + dy * dy + dz * dz).sqrt(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cube [0, 1]^3 with outward normals, each face split into n x n squares.
+    fn cube(n: usize) -> (Vec<Vector3>, Vec<Triplet>) {
+        let mut coordinates = Vec::new();
+        let mut triangles = Vec::new();
+        let h = 1.0 / n as f64;
+
+        // (origin, u, v) of each face, with u x v pointing outward
+        let faces = [
+            ([0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+            ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            ([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+            ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+            ([0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+            ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        ];
+        for (origin, u, v) in faces {
+            let o = coordinates.len();
+            for i in 0..=n {
+                for j in 0..=n {
+                    let (s, t) = (i as f64 * h, j as f64 * h);
+                    coordinates.push(Vector3 {
+                        x: origin[0] + s * u[0] + t * v[0],
+                        y: origin[1] + s * u[1] + t * v[1],
+                        z: origin[2] + s * u[2] + t * v[2],
+                    });
+                }
+            }
+            for i in 0..n {
+                for j in 0..n {
+                    let p = o + i * (n + 1) + j;
+                    let q = p + n + 1;
+                    triangles.push((p, q, q + 1));
+                    triangles.push((p, q + 1, p + 1));
+                }
+            }
+        }
+
+        (coordinates, triangles)
+    }
+
+    #[test]
+    fn test_inside_and_outside() {
+        let (coordinates, triangles) = cube(8);
+        let winding_number = WindingNumber::new(&coordinates, &triangles);
+
+        let inside = Vector3 {
+            x: 0.3,
+            y: 0.6,
+            z: 0.5,
+        };
+        let outside = Vector3 {
+            x: 1.3,
+            y: 0.6,
+            z: 0.5,
+        };
+        let far_away = Vector3 {
+            x: 30.0,
+            y: -20.0,
+            z: 10.0,
+        };
+
+        assert!((winding_number.evaluate(&inside, None) - 1.0).abs() < 1.0e-2);
+        assert!(winding_number.evaluate(&outside, None).abs() < 1.0e-2);
+        assert!(winding_number.evaluate(&far_away, None).abs() < 1.0e-2);
+    }
+
+    #[test]
+    fn test_vertex_on_flat_face() {
+        let (coordinates, triangles) = cube(4);
+        let winding_number = WindingNumber::new(&coordinates, &triangles);
+
+        // center vertex of the first face
+        let vertex = 2 * 5 + 2;
+        let w = winding_number.evaluate(&coordinates[vertex], Some(vertex));
+        assert!((w - 0.5).abs() < 1.0e-2);
+    }
+}
@@ -0,0 +1,103 @@
+use mesh::vector::{vec_cross_vec, vec_dot_vec, vec_minus_vec, Vector3};
+use mesh::Triplet;
+
+use crate::triangle_neighbors;
+
+/// For each triangle, the number of its connected component, where triangles
+/// are connected through manifold edges. Components are numbered in order of
+/// their first triangle.
+pub fn connected_components(triangles: &[Triplet]) -> Vec<usize> {
+    let neighbors = triangle_neighbors(triangles);
+    let mut components = vec![usize::MAX; triangles.len()];
+    let mut num_components = 0;
+
+    for start_triangle in 0..triangles.len() {
+        if components[start_triangle] != usize::MAX {
+            continue;
+        }
+        let mut stack = vec![start_triangle];
+        components[start_triangle] = num_components;
+        while let Some(triangle) = stack.pop() {
+            for &neighbor in &neighbors[triangle] {
+                if components[neighbor] == usize::MAX {
+                    components[neighbor] = num_components;
+                    stack.push(neighbor);
+                }
+            }
+        }
+        num_components += 1;
+    }
+
+    components
+}
+
+/// Number of triangles and area of a connected component.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Component {
+    pub num_triangles: usize,
+    pub area: f64,
+}
+
+pub fn measure_components(
+    coordinates: &[Vector3],
+    triangles: &[Triplet],
+    components: &[usize],
+) -> Vec<Component> {
+    let num_components = components.iter().max().map_or(0, |m| m + 1);
+    let mut measures = vec![
+        Component {
+            num_triangles: 0,
+            area: 0.0,
+        };
+        num_components
+    ];
+    for (&(a, b, c), &component) in triangles.iter().zip(components) {
+        let (a, b, c) = (&coordinates[a], &coordinates[b], &coordinates[c]);
+        let normal = vec_cross_vec(&vec_minus_vec(b, a), &vec_minus_vec(c, a));
+        measures[component].num_triangles += 1;
+        measures[component].area += 0.5 * vec_dot_vec(&normal, &normal).sqrt();
+    }
+
+    measures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_components() {
+        let point = |x, y| Vector3 { x, y, z: 0.0 };
+        let coordinates = vec![
+            point(0.0, 0.0),
+            point(2.0, 0.0),
+            point(2.0, 2.0),
+            point(0.0, 2.0),
+            point(5.0, 5.0),
+            point(6.0, 5.0),
+            point(5.0, 6.0),
+            point(3.0, 3.0),
+            point(2.0, 3.0),
+        ];
+        // a square, a separate triangle, and a triangle touching the square
+        // only in a vertex
+        let triangles = vec![(0, 1, 2), (4, 5, 6), (0, 2, 3), (2, 7, 8)];
+        let components = connected_components(&triangles);
+        assert_eq!(components, vec![0, 1, 0, 2]);
+
+        let measures = measure_components(&coordinates, &triangles, &components);
+        assert_eq!(
+            measures[..2],
+            [
+                Component {
+                    num_triangles: 2,
+                    area: 4.0
+                },
+                Component {
+                    num_triangles: 1,
+                    area: 0.5
+                }
+            ]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::{HashMap, HashSet};
+
+use mesh::vector::{vec_cross_vec, vec_dot_vec, vec_minus_vec, Vector3};
+use mesh::Triplet;
+
+use crate::ordered;
+
+/// Boundary loops, each as the vertices in the order the adjacent triangles
+/// traverse them, together with the position of the triangle owning the
+/// edge from the first to the second vertex. Loops through a vertex with
+/// more than one outgoing boundary edge are left out since they cannot be
+/// told apart.
+pub fn find_boundary_loops(triangles: &[Triplet]) -> Vec<(Vec<usize>, usize)> {
+    let half_edges: HashSet<(usize, usize)> = triangles
+        .iter()
+        .flat_map(|&(a, b, c)| [(a, b), (b, c), (c, a)])
+        .collect();
+
+    // boundary edges leaving each vertex, with their triangle
+    let mut outgoing: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+    for (i, &(a, b, c)) in triangles.iter().enumerate() {
+        for (u, v) in [(a, b), (b, c), (c, a)] {
+            if !half_edges.contains(&(v, u)) {
+                outgoing.entry(u).or_default().push((v, i));
+            }
+        }
+    }
+
+    let mut starts: Vec<usize> = outgoing.keys().copied().collect();
+    starts.sort_unstable();
+
+    let mut visited: HashSet<usize> = HashSet::new();
+    let mut loops = Vec::new();
+    for start in starts {
+        if visited.contains(&start) {
+            continue;
+        }
+        let mut vertices = vec![start];
+        let mut ambiguous = false;
+        let mut vertex = start;
+        loop {
+            visited.insert(vertex);
+            let next = &outgoing[&vertex];
+            ambiguous |= next.len() > 1;
+            vertex = next[0].0;
+            if vertex == start || visited.contains(&vertex) || !outgoing.contains_key(&vertex) {
+                ambiguous |= vertex != start;
+                break;
+            }
+            vertices.push(vertex);
+        }
+        if !ambiguous {
+            loops.push((vertices, outgoing[&start][0].1));
+        }
+    }
+
+    loops
+}
+
+fn distance(p: &Vector3, q: &Vector3) -> f64 {
+    let d = vec_minus_vec(p, q);
+    vec_dot_vec(&d, &d).sqrt()
+}
+
+fn area(p: &Vector3, q: &Vector3, r: &Vector3) -> f64 {
+    let n = vec_cross_vec(&vec_minus_vec(q, p), &vec_minus_vec(r, p));
+    0.5 * vec_dot_vec(&n, &n).sqrt()
+}
+
+pub fn perimeter(coordinates: &[Vector3], vertices: &[usize]) -> f64 {
+    (0..vertices.len())
+        .map(|i| {
+            let j = (i + 1) % vertices.len();
+            distance(&coordinates[vertices[i]], &coordinates[vertices[j]])
+        })
+        .sum()
+}
+
+/// Edges of the triangles, with the smaller vertex first.
+pub fn edges(triangles: &[Triplet]) -> HashSet<(usize, usize)> {
+    triangles
+        .iter()
+        .flat_map(|&(a, b, c)| [ordered(a, b), ordered(b, c), ordered(c, a)])
+        .collect()
+}
+
+/// Closes a boundary loop with the triangulation of smallest total area,
+/// wound so that it continues the winding of the triangles around it. Edges
+/// across the loop which are among `existing` edges are not used, since a
+/// third triangle at such an edge would make it non-manifold; `None` if the
+/// loop cannot be closed without them.
+pub fn fill_loop(
+    coordinates: &[Vector3],
+    boundary: &[usize],
+    existing: &HashSet<(usize, usize)>,
+) -> Option<Vec<Triplet>> {
+    // walk the loop backwards so that shared edges are traversed the other way
+    let polygon: Vec<usize> = boundary.iter().rev().copied().collect();
+    let n = polygon.len();
+    let point = |i: usize| &coordinates[polygon[i]];
+
+    // cost[i][j]: smallest area of the polygon i, i + 1, ..., j, split[i][j]
+    // the third corner of the triangle on the edge from i to j
+    let mut cost = vec![vec![0.0; n]; n];
+    let mut split = vec![vec![0; n]; n];
+    for length in 2..n {
+        for i in 0..n - length {
+            let j = i + length;
+            cost[i][j] = f64::INFINITY;
+            let closing = i == 0 && j == n - 1;
+            if !closing && existing.contains(&ordered(polygon[i], polygon[j])) {
+                continue;
+            }
+            for k in i + 1..j {
+                let c = cost[i][k] + cost[k][j] + area(point(i), point(k), point(j));
+                if c < cost[i][j] {
+                    cost[i][j] = c;
+                    split[i][j] = k;
+                }
+            }
+        }
+    }
+
+    if cost[0][n - 1].is_infinite() {
+        return None;
+    }
+
+    let mut triangles = Vec::new();
+    let mut to_split = vec![(0, n - 1)];
+    while let Some((i, j)) = to_split.pop() {
+        if j - i < 2 {
+            continue;
+        }
+        let k = split[i][j];
+        triangles.push((polygon[i], polygon[k], polygon[j]));
+        to_split.push((i, k));
+        to_split.push((k, j));
+    }
+
+    Some(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_hole() {
+        // a square pyramid without its base
+        let point = |x, y, z| Vector3 { x, y, z };
+        let coordinates = vec![
+            point(0.0, 0.0, 0.0),
+            point(1.0, 0.0, 0.0),
+            point(1.0, 1.0, 0.0),
+            point(0.0, 1.0, 0.0),
+            point(0.5, 0.5, 1.0),
+        ];
+        let triangles = vec![(0, 1, 4), (1, 2, 4), (2, 3, 4), (3, 0, 4)];
+
+        let loops = find_boundary_loops(&triangles);
+        assert_eq!(loops, vec![(vec![0, 1, 2, 3], 0)]);
+        assert_eq!(perimeter(&coordinates, &loops[0].0), 4.0);
+
+        let filling = fill_loop(&coordinates, &loops[0].0, &edges(&triangles)).unwrap();
+        assert_eq!(filling.len(), 2);
+        let mut closed = triangles.clone();
+        closed.extend(filling);
+        assert!(find_boundary_loops(&closed).is_empty());
+
+        // every edge is now traversed once in each direction
+        let half_edges: HashSet<(usize, usize)> = closed
+            .iter()
+            .flat_map(|&(a, b, c)| [(a, b), (b, c), (c, a)])
+            .collect();
+        assert_eq!(half_edges.len(), 3 * closed.len());
+        assert!(half_edges
+            .iter()
+            .all(|&(u, v)| half_edges.contains(&(v, u))));
+    }
+
+    #[test]
+    fn test_existing_edges_are_not_used() {
+        // a square whose diagonal from 0 to 2 is much shorter, but already an
+        // edge elsewhere in the mesh
+        let point = |x, y| Vector3 { x, y, z: 0.0 };
+        let coordinates = vec![
+            point(0.0, 0.0),
+            point(2.0, -0.5),
+            point(1.0, 1.0),
+            point(-0.5, 2.0),
+        ];
+        let boundary = [0, 1, 2, 3];
+        let mut existing = HashSet::from([(0, 1), (1, 2), (2, 3), (0, 3)]);
+        let filling = fill_loop(&coordinates, &boundary, &existing).unwrap();
+        assert!(edges(&filling).contains(&(0, 2)));
+
+        existing.insert((0, 2));
+        let filling = fill_loop(&coordinates, &boundary, &existing).unwrap();
+        assert!(edges(&filling).contains(&(1, 3)));
+        assert!(!edges(&filling).contains(&(0, 2)));
+
+        existing.insert((1, 3));
+        assert_eq!(fill_loop(&coordinates, &boundary, &existing), None);
+    }
+}
@@ -1,12 +1,19 @@
-#![allow(clippy::type_complexity)]
-
+use anyhow::{ensure, Context, Result};
 use clap::Parser;
 use std::collections::{HashMap, HashSet};
 
-mod io;
+use mesh::io::Format;
+use mesh::vector::Vector3;
+use mesh::{io, Triplet};
+
+mod components;
+mod holes;
+mod nonmanifold;
+mod outward;
+mod validate;
 
-#[macro_use]
-extern crate anyhow;
+use crate::nonmanifold::Repair;
+use crate::validate::{Check, Report, ReportFormat};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -16,52 +23,450 @@ struct Args {
     input_file: String,
 
     /// Output file
-    #[arg(short, long)]
-    output_file: String,
+    #[arg(short, long, required_unless_present = "validate")]
+    output_file: Option<String>,
+
+    /// Format of the input file: "text", "msh", "stl", "obj", "ply",
+    /// "gifti", "freesurfer" or "binary". By default it is chosen by file
+    /// extension.
+    #[arg(long)]
+    input_format: Option<Format>,
+
+    /// Format of the output file: "text", "stl", "stl-ascii", "obj", "ply",
+    /// "ply-ascii", "vtk", "vtp", "gifti", "freesurfer", "binary" or
+    /// "binary-f32". By default it is chosen by file extension.
+    #[arg(long)]
+    output_format: Option<Format>,
+
+    /// Triangle indices in the text input start at 1, as in Gmsh, instead
+    /// of 0
+    #[arg(long)]
+    one_based: bool,
+
+    /// Also write the whole input mesh with a "drop_reason" per triangle,
+    /// as .vtk or .vtp for ParaView: 0 kept, 1 duplicate, 2 orientation
+    /// conflict, 3 double boundary vertex, 4 hourglass vertex, 5 small
+    /// connected component, 6 non-manifold edge, and the
+    /// offending vertex as "drop_vertex" (-1 if there is none)
+    #[arg(long)]
+    annotated_output: Option<String>,
+
+    /// Write one CSV line per removed triangle with the stage that removed it,
+    /// the reason and the offending vertex, and print how many triangles
+    /// each stage removed
+    #[arg(long)]
+    explain: Option<String>,
+
+    /// Repeat orientation and the removal of double boundary and hourglass
+    /// vertices at most this many times, until they remove nothing more.
+    /// How many triangles each iteration removed is printed with --explain.
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    max_iterations: u64,
+
+    /// How to repair edges shared by more than two triangles
+    #[arg(long, value_enum, default_value_t = Repair::Keep)]
+    non_manifold_edges: Repair,
+
+    /// Only keep the connected component with the largest area
+    #[arg(long)]
+    keep_largest: bool,
+
+    /// Remove connected components with fewer triangles
+    #[arg(long)]
+    min_component_triangles: Option<usize>,
+
+    /// Remove connected components with a smaller area, in squared mesh
+    /// units
+    #[arg(long)]
+    min_component_area: Option<f64>,
+
+    /// Close holes left by the removed triangles, or present in the input,
+    /// with the triangulation of smallest area. Large openings such as the
+    /// neck cut are left alone, see --max-hole-edges and
+    /// --max-hole-perimeter.
+    #[arg(long)]
+    fill_holes: bool,
+
+    /// Only fill holes with at most this many boundary edges
+    #[arg(long, default_value_t = 100)]
+    max_hole_edges: usize,
+
+    /// Only fill holes with at most this perimeter, in mesh units
+    #[arg(long)]
+    max_hole_perimeter: Option<f64>,
+
+    /// Keep the winding each component happens to get from its first
+    /// triangle instead of flipping components so that normals point outward
+    #[arg(long)]
+    keep_winding: bool,
+
+    /// Only check the input mesh and print a report instead of cleaning it
+    /// up. Exits with an error if one of the --fail-on checks fails.
+    /// Out-of-range indices are reported for text input, other readers
+    /// reject them.
+    #[arg(long)]
+    validate: bool,
+
+    /// Format of the validation report
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    report_format: ReportFormat,
+
+    /// Write the validation report to this file instead of standard output
+    #[arg(long)]
+    report: Option<String>,
+
+    /// Checks which make validation fail, separated by commas
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "out-of-range-indices,degenerate-triangles,duplicate-triangles,non-manifold-edges,non-manifold-vertices,inconsistent-orientation"
+    )]
+    fail_on: Vec<Check>,
+}
+
+/// Why a triangle was removed, with the vertex responsible if there is one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum DropReason {
+    Duplicate,
+    Orientation,
+    DoubleBoundary(usize),
+    Hourglass(usize),
+    SmallComponent,
+    NonManifoldEdge,
+}
+
+/// Descriptions of the cleanup stages by their number. Numbers stay the same
+/// for annotated output, so they do not follow the order the stages run in.
+const STAGES: [&str; 6] = [
+    "duplicate",
+    "orientation conflict",
+    "double boundary vertex",
+    "hourglass vertex",
+    "small component",
+    "non-manifold edge",
+];
+
+impl DropReason {
+    /// Number of the stage which removed the triangle, starting at 1 as in
+    /// annotated output.
+    fn stage(&self) -> usize {
+        match self {
+            DropReason::Duplicate => 1,
+            DropReason::Orientation => 2,
+            DropReason::DoubleBoundary(_) => 3,
+            DropReason::Hourglass(_) => 4,
+            DropReason::SmallComponent => 5,
+            DropReason::NonManifoldEdge => 6,
+        }
+    }
+
+    fn vertex(&self) -> Option<usize> {
+        match self {
+            DropReason::DoubleBoundary(v) | DropReason::Hourglass(v) => Some(*v),
+            _ => None,
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
+    if let Err(error) = run(&args) {
+        eprintln!("error: {:#}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &Args) -> Result<()> {
+    if let Some(file_name) = &args.annotated_output {
+        ensure!(
+            Format::from_file_name(file_name).writes_scalars(),
+            "annotated output {} would lose its scalars, use .vtk or .vtp",
+            file_name
+        );
+    }
+
+    let input_format = args
+        .input_format
+        .unwrap_or_else(|| Format::from_file_name(&args.input_file));
+    let first_index = args.one_based as usize;
+
+    if args.validate {
+        // triangles referring to missing vertices are reported, not rejected
+        let mesh = io::read_mesh_unchecked(&args.input_file, input_format, first_index)?;
+        return validate(args, &mesh);
+    }
+
+    let mesh = io::read_mesh_indexed_from(&args.input_file, input_format, first_index)?;
+
+    let output_file = args.output_file.as_deref().unwrap();
+    let output_format = args
+        .output_format
+        .unwrap_or_else(|| Format::from_file_name(output_file));
+
+    // remaining triangles together with their index in the input
+    let mut dropped: Vec<Option<DropReason>> = vec![None; mesh.triangles.len()];
+    let mut seen: HashSet<Triplet> = HashSet::new();
+    let mut kept: Vec<(usize, Triplet)> = Vec::new();
+    for (i, triangle) in mesh.triangles.iter().enumerate() {
+        if seen.insert(*triangle) {
+            kept.push((i, *triangle));
+        } else {
+            dropped[i] = Some(DropReason::Duplicate);
+        }
+    }
 
-    let (coordinates, triangles) = io::read_mesh(&args.input_file).unwrap();
+    // split vertices are appended to the input vertices
+    let mut coordinates = mesh.vertices.clone();
+    let mut vertex_sources: Vec<usize> = (0..mesh.vertices.len()).collect();
+    match args.non_manifold_edges {
+        Repair::Keep => {}
+        Repair::Split => {
+            let triangles: Vec<Triplet> = kept.iter().map(|(_, t)| *t).collect();
+            let (triangles, copies, unresolved) =
+                nonmanifold::split_sheets(&coordinates, &triangles);
+            if !unresolved.is_empty() {
+                let edges: Vec<String> = unresolved
+                    .iter()
+                    .map(|(u, v)| format!("{}-{}", u, v))
+                    .collect();
+                eprintln!(
+                    "warning: could not split non-manifold edges {}",
+                    edges.join(", ")
+                );
+            }
+            for (entry, triangle) in kept.iter_mut().zip(triangles) {
+                entry.1 = triangle;
+            }
+            coordinates.extend(copies.iter().map(|&v| mesh.vertices[v]));
+            vertex_sources.extend(copies);
+        }
+        Repair::Remove => {
+            let triangles: Vec<Triplet> = kept.iter().map(|(_, t)| *t).collect();
+            let extra = nonmanifold::find_extra_triangles(&coordinates, &triangles);
+            kept = kept
+                .into_iter()
+                .enumerate()
+                .filter_map(|(position, (i, t))| {
+                    if extra.contains(&position) {
+                        dropped[i] = Some(DropReason::NonManifoldEdge);
+                        None
+                    } else {
+                        Some((i, t))
+                    }
+                })
+                .collect();
+        }
+    }
 
-    let triangles = orient_triangles(&triangles, true);
-    let triangles = remove_double_boundary_indices(&triangles);
-    let triangles = remove_hourglass_indices(&triangles);
+    let (remaining, iterations) = make_manifold(kept, &mut dropped, args.max_iterations as usize);
+    kept = remaining;
+    let converged = iterations.last().is_some_and(|counts| counts == &[0; 3]);
+    if !converged {
+        eprintln!(
+            "warning: no fixed point after {} iterations, the output may not be manifold",
+            args.max_iterations
+        );
+    }
 
-    let (coordinates, triangles) = remove_unreferenced_indices(&coordinates, &triangles);
+    if args.keep_largest
+        || args.min_component_triangles.is_some()
+        || args.min_component_area.is_some()
+    {
+        kept = drop_small_components(args, &coordinates, kept, &mut dropped);
+    }
 
-    io::write_mesh(&args.output_file, &coordinates, &triangles);
+    let mut num_holes = 0;
+    let mut num_filling = 0;
+    let mut num_unfilled = 0;
+    if args.fill_holes {
+        let triangles: Vec<Triplet> = kept.iter().map(|(_, t)| *t).collect();
+        let mut edges = holes::edges(&triangles);
+        for (boundary, owner) in holes::find_boundary_loops(&triangles) {
+            let perimeter = holes::perimeter(&coordinates, &boundary);
+            if boundary.len() > args.max_hole_edges
+                || args.max_hole_perimeter.is_some_and(|max| perimeter > max)
+            {
+                continue;
+            }
+            let Some(filling) = holes::fill_loop(&coordinates, &boundary, &edges) else {
+                num_unfilled += 1;
+                continue;
+            };
+            // new triangles take tags and scalars from a triangle next to them
+            let source = kept[owner].0;
+            num_holes += 1;
+            num_filling += filling.len();
+            edges.extend(holes::edges(&filling));
+            kept.extend(filling.into_iter().map(|t| (source, t)));
+        }
+    }
+
+    let sources: Vec<usize> = kept.iter().map(|(i, _)| *i).collect();
+    let mut triangles: Vec<Triplet> = kept.iter().map(|(_, t)| *t).collect();
+    let num_flipped = if args.keep_winding {
+        0
+    } else {
+        let components = components::connected_components(&triangles);
+        outward::orient_outward(&coordinates, &mut triangles, &components)
+    };
+
+    if let Some(file_name) = &args.annotated_output {
+        let mut annotated = mesh.clone();
+        let reasons = dropped.iter().map(|r| r.map_or(0.0, |r| r.stage() as f64));
+        let vertices = dropped
+            .iter()
+            .map(|r| r.and_then(|r| r.vertex()).map_or(-1.0, |v| v as f64));
+        annotated
+            .triangle_scalars
+            .push(("drop_reason".to_string(), reasons.collect()));
+        annotated
+            .triangle_scalars
+            .push(("drop_vertex".to_string(), vertices.collect()));
+        io::write_mesh(file_name, &annotated)?;
+    }
+
+    if let Some(file_name) = &args.explain {
+        write_explanation(file_name, &dropped)?;
+        print!("{}", summary(&dropped));
+        for (i, counts) in iterations.iter().enumerate() {
+            println!(
+                "iteration {}: removed {} ({}), {} ({}), {} ({})",
+                i + 1,
+                counts[0],
+                STAGES[1],
+                counts[1],
+                STAGES[2],
+                counts[2],
+                STAGES[3]
+            );
+        }
+        if args.non_manifold_edges == Repair::Split {
+            println!(
+                "split non-manifold edges with {} new vertices",
+                coordinates.len() - mesh.vertices.len()
+            );
+        }
+        if args.fill_holes {
+            println!(
+                "filled {} holes with {} triangles, left {} which would need existing edges",
+                num_holes, num_filling, num_unfilled
+            );
+        }
+        println!("flipped {} components to point outward", num_flipped);
+    }
+
+    // kept triangles are still in input order, followed by the filled holes,
+    // but may have been flipped
+    let mut mesh = mesh
+        .select_triangles(&sources)
+        .select_vertices(&vertex_sources);
+    mesh.triangles = triangles;
+
+    io::write_mesh_as(
+        output_file,
+        &mesh.remove_unreferenced_vertices(),
+        output_format,
+    )
 }
 
-fn remove_unreferenced_indices(
-    coordinates: &[(f64, f64, f64)],
-    triangles: &HashSet<(usize, usize, usize)>,
-) -> (Vec<(f64, f64, f64)>, HashSet<(usize, usize, usize)>) {
-    let used_indices: HashSet<usize> = triangles
-        .iter()
-        .flat_map(|&(a, b, c)| vec![a, b, c])
-        .collect();
+fn validate(args: &Args, mesh: &mesh::Mesh) -> Result<()> {
+    let report = Report::new(mesh);
+    let failed = report.failed(&args.fail_on);
+    let contents = report.format(args.report_format, &failed);
+    match &args.report {
+        Some(file_name) => std::fs::write(file_name, contents)
+            .context(format!("unable to write file {}", file_name))?,
+        None => print!("{}", contents),
+    }
 
-    let mut used_indices: Vec<usize> = used_indices.into_iter().collect();
-    used_indices.sort_unstable();
+    let names: Vec<String> = failed.iter().map(|check| check.name()).collect();
+    ensure!(names.is_empty(), "failed checks: {}", names.join(", "));
+    Ok(())
+}
 
-    let mut new_coordinates = Vec::new();
-    let mut point_index_map: HashMap<usize, usize> = HashMap::new();
-    for (i, j) in used_indices.iter().enumerate() {
-        point_index_map.insert(*j, i);
-        new_coordinates.push(coordinates[*j]);
+/// Runs orientation and the removal of double boundary and hourglass
+/// vertices until they remove nothing more, at most `max_iterations` times,
+/// since removing triangles can create new problem vertices. Returns the
+/// remaining triangles and, for each iteration, how many triangles the three
+/// stages removed.
+fn make_manifold(
+    mut kept: Vec<(usize, Triplet)>,
+    dropped: &mut [Option<DropReason>],
+    max_iterations: usize,
+) -> (Vec<(usize, Triplet)>, Vec<[usize; 3]>) {
+    let mut iterations = Vec::new();
+    for _ in 0..max_iterations {
+        let mut counts = [0; 3];
+
+        let num_before = kept.len();
+        let triangles: Vec<Triplet> = kept.iter().map(|(_, t)| *t).collect();
+        let oriented = orient_triangles(&triangles, true);
+        kept = kept
+            .iter()
+            .zip(oriented)
+            .filter_map(|(&(i, _), triangle)| {
+                if triangle.is_none() {
+                    dropped[i] = Some(DropReason::Orientation);
+                }
+                triangle.map(|t| (i, t))
+            })
+            .collect();
+        counts[0] = num_before - kept.len();
+
+        let num_before = kept.len();
+        let triangles: Vec<Triplet> = kept.iter().map(|(_, t)| *t).collect();
+        let bad_indices = find_double_boundary_indices(&triangles);
+        kept = drop_triangles_touching(kept, &bad_indices, DropReason::DoubleBoundary, dropped);
+        counts[1] = num_before - kept.len();
+
+        let num_before = kept.len();
+        let triangles: Vec<Triplet> = kept.iter().map(|(_, t)| *t).collect();
+        let bad_indices = find_hourglass_indices(&triangles);
+        kept = drop_triangles_touching(kept, &bad_indices, DropReason::Hourglass, dropped);
+        counts[2] = num_before - kept.len();
+
+        iterations.push(counts);
+        if counts == [0; 3] {
+            break;
+        }
     }
 
-    let mut new_triangles: HashSet<(usize, usize, usize)> = HashSet::new();
-    for (a, b, c) in triangles {
-        let a_new = *point_index_map.get(a).unwrap();
-        let b_new = *point_index_map.get(b).unwrap();
-        let c_new = *point_index_map.get(c).unwrap();
-        new_triangles.insert((a_new, b_new, c_new));
+    (kept, iterations)
+}
+
+/// CSV with one line per removed triangle.
+fn write_explanation(file_name: &str, dropped: &[Option<DropReason>]) -> Result<()> {
+    let mut contents = String::from("triangle,stage,reason,vertex\n");
+    for (i, reason) in dropped.iter().enumerate() {
+        if let Some(reason) = reason {
+            let vertex = reason.vertex().map_or(String::new(), |v| v.to_string());
+            contents += &format!(
+                "{},{},{},{}\n",
+                i,
+                reason.stage(),
+                STAGES[reason.stage() - 1],
+                vertex
+            );
+        }
+    }
+    std::fs::write(file_name, contents).context(format!("unable to write file {}", file_name))
+}
+
+/// Number of triangles removed by each stage.
+fn summary(dropped: &[Option<DropReason>]) -> String {
+    let mut counts = [0; STAGES.len()];
+    for reason in dropped.iter().flatten() {
+        counts[reason.stage() - 1] += 1;
     }
 
-    (new_coordinates, new_triangles)
+    let mut lines = String::new();
+    for (stage, (description, count)) in STAGES.iter().zip(counts).enumerate() {
+        lines += &format!("stage {} ({}): removed {}\n", stage + 1, description, count);
+    }
+    let num_kept = dropped.iter().filter(|r| r.is_none()).count();
+    lines += &format!("kept {} of {} triangles\n", num_kept, dropped.len());
+    lines
 }
 
 fn ordered(a: usize, b: usize) -> (usize, usize) {
@@ -72,59 +477,63 @@ fn ordered(a: usize, b: usize) -> (usize, usize) {
     }
 }
 
-fn triangle_neighbors(
-    triangles: &HashSet<(usize, usize, usize)>,
-) -> HashMap<(usize, usize, usize), Vec<(usize, usize, usize)>> {
-    let mut edge_to_triangles: HashMap<(usize, usize), Vec<(usize, usize, usize)>> = HashMap::new();
-    for (a, b, c) in triangles {
+/// For each triangle, the positions of the triangles which share a manifold
+/// edge with it, in the order of the triangle list.
+fn triangle_neighbors(triangles: &[Triplet]) -> Vec<Vec<usize>> {
+    let mut edge_to_triangles: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (i, (a, b, c)) in triangles.iter().enumerate() {
         edge_to_triangles
             .entry(ordered(*a, *b))
             .or_default()
-            .push((*a, *b, *c));
+            .push(i);
         edge_to_triangles
             .entry(ordered(*b, *c))
             .or_default()
-            .push((*a, *b, *c));
+            .push(i);
         edge_to_triangles
             .entry(ordered(*c, *a))
             .or_default()
-            .push((*a, *b, *c));
+            .push(i);
     }
 
-    let mut neighbors: HashMap<(usize, usize, usize), Vec<(usize, usize, usize)>> = HashMap::new();
-    for (_edge, triangles) in edge_to_triangles {
-        if triangles.len() == 2 {
-            let (t1, t2) = (triangles[0], triangles[1]);
-            neighbors.entry(t1).or_default().push(t2);
-            neighbors.entry(t2).or_default().push(t1);
+    let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); triangles.len()];
+    for (i, (a, b, c)) in triangles.iter().enumerate() {
+        for edge in [ordered(*a, *b), ordered(*b, *c), ordered(*c, *a)] {
+            let sharing = &edge_to_triangles[&edge];
+            if sharing.len() == 2 {
+                let other = if sharing[0] == i {
+                    sharing[1]
+                } else {
+                    sharing[0]
+                };
+                neighbors[i].push(other);
+            }
         }
     }
 
     neighbors
 }
 
-/// Start with start_triangle
+/// Start with the first triangle
 /// Then visit all its neighbors before going anywhere else
 /// Then visit all neighbors of visited triangles
-/// And so on ...
-fn visit_all_triangles(
-    triangles: &HashSet<(usize, usize, usize)>,
-    start_triangle: (usize, usize, usize),
-) -> Vec<(usize, usize, usize)> {
-    let mut visited: HashSet<(usize, usize, usize)> = HashSet::new();
-    let mut visit_list: Vec<(usize, usize, usize)> = Vec::new();
+/// And so on until the connected component is done, then continue with the
+/// first triangle not visited yet
+fn visit_all_triangles(triangles: &[Triplet]) -> Vec<usize> {
+    let mut visited = vec![false; triangles.len()];
+    let mut visit_list: Vec<usize> = Vec::new();
     let neighbors = triangle_neighbors(triangles);
 
-    let mut to_visit = std::collections::VecDeque::new();
-    to_visit.push_back(start_triangle);
-    while let Some(triangle) = to_visit.pop_front() {
-        if visited.contains(&triangle) {
-            continue;
-        }
-        visited.insert(triangle);
-        visit_list.push(triangle);
-        if let Some(neighbors) = neighbors.get(&triangle) {
-            for neighbor in neighbors {
+    for start_triangle in 0..triangles.len() {
+        let mut to_visit = std::collections::VecDeque::new();
+        to_visit.push_back(start_triangle);
+        while let Some(triangle) = to_visit.pop_front() {
+            if visited[triangle] {
+                continue;
+            }
+            visited[triangle] = true;
+            visit_list.push(triangle);
+            for neighbor in &neighbors[triangle] {
                 to_visit.push_back(*neighbor);
             }
         }
@@ -133,28 +542,101 @@ fn visit_all_triangles(
     visit_list
 }
 
-fn orient_triangles(
-    triangles: &HashSet<(usize, usize, usize)>,
-    drop_bad_triangles: bool,
-) -> HashSet<(usize, usize, usize)> {
-    let start_triangle = *triangles.iter().next().unwrap();
-    let visit_list = visit_all_triangles(triangles, start_triangle);
+/// Removes the connected components which are not the largest one if
+/// --keep-largest is given, or too small for --min-component-triangles or
+/// --min-component-area, and prints what was removed.
+fn drop_small_components(
+    args: &Args,
+    coordinates: &[Vector3],
+    kept: Vec<(usize, Triplet)>,
+    dropped: &mut [Option<DropReason>],
+) -> Vec<(usize, Triplet)> {
+    let triangles: Vec<Triplet> = kept.iter().map(|(_, t)| *t).collect();
+    let components = components::connected_components(&triangles);
+    let measures = components::measure_components(coordinates, &triangles, &components);
+
+    let largest =
+        (0..measures.len()).max_by(|&i, &j| measures[i].area.total_cmp(&measures[j].area));
+    let discard: Vec<bool> = measures
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            (args.keep_largest && Some(i) != largest)
+                || args
+                    .min_component_triangles
+                    .is_some_and(|n| m.num_triangles < n)
+                || args.min_component_area.is_some_and(|a| m.area < a)
+        })
+        .collect();
+
+    for (i, m) in measures.iter().enumerate() {
+        if discard[i] {
+            println!(
+                "removed component {} with {} triangles and area {}",
+                i, m.num_triangles, m.area
+            );
+        }
+    }
+    println!(
+        "kept {} of {} components",
+        discard.iter().filter(|d| !**d).count(),
+        measures.len()
+    );
+
+    kept.into_iter()
+        .zip(components)
+        .filter_map(|((i, t), component)| {
+            if discard[component] {
+                dropped[i] = Some(DropReason::SmallComponent);
+                None
+            } else {
+                Some((i, t))
+            }
+        })
+        .collect()
+}
+
+/// Removes the triangles which contain one of `bad_indices` and records
+/// `reason` with the first such vertex for them.
+fn drop_triangles_touching(
+    kept: Vec<(usize, Triplet)>,
+    bad_indices: &HashSet<usize>,
+    reason: fn(usize) -> DropReason,
+    dropped: &mut [Option<DropReason>],
+) -> Vec<(usize, Triplet)> {
+    kept.into_iter()
+        .filter(|&(i, (a, b, c))| {
+            let bad = [a, b, c].into_iter().find(|v| bad_indices.contains(v));
+            if let Some(vertex) = bad {
+                dropped[i] = Some(reason(vertex));
+            }
+            bad.is_none()
+        })
+        .collect()
+}
+
+/// Returns the oriented triangles in their original order, `None` for the
+/// dropped ones. Each connected component keeps the winding of its first
+/// triangle.
+fn orient_triangles(triangles: &[Triplet], drop_bad_triangles: bool) -> Vec<Option<Triplet>> {
+    let visit_list = visit_all_triangles(triangles);
 
     let mut half_edges: HashSet<(usize, usize)> = HashSet::new();
-    let mut oriented_triangles: HashSet<(usize, usize, usize)> = HashSet::new();
-    for (a, b, c) in visit_list {
+    let mut oriented_triangles: Vec<Option<Triplet>> = vec![None; triangles.len()];
+    for i in visit_list {
+        let (a, b, c) = triangles[i];
         if half_edges.contains(&(a, b))
             || half_edges.contains(&(b, c))
             || half_edges.contains(&(c, a))
         {
             if !drop_bad_triangles {
-                oriented_triangles.insert((c, b, a));
+                oriented_triangles[i] = Some((c, b, a));
                 half_edges.insert((c, b));
                 half_edges.insert((b, a));
                 half_edges.insert((a, c));
             }
         } else {
-            oriented_triangles.insert((a, b, c));
+            oriented_triangles[i] = Some((a, b, c));
             half_edges.insert((a, b));
             half_edges.insert((b, c));
             half_edges.insert((c, a));
@@ -164,9 +646,8 @@ fn orient_triangles(
     oriented_triangles
 }
 
-fn remove_double_boundary_indices(
-    triangles: &HashSet<(usize, usize, usize)>,
-) -> HashSet<(usize, usize, usize)> {
+/// Vertices which lie on more than one boundary loop.
+fn find_double_boundary_indices(triangles: &[Triplet]) -> HashSet<usize> {
     let mut edge_to_vertex: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
     for (a, b, c) in triangles {
         edge_to_vertex.entry(ordered(*a, *b)).or_default().push(*c);
@@ -182,24 +663,11 @@ fn remove_double_boundary_indices(
         }
     }
 
-    let double_boundary_indices: HashSet<usize> = boundary_indices
+    boundary_indices
         .iter()
         .filter(|(_, &v)| v > 2)
         .map(|(&k, _)| k)
-        .collect();
-
-    let mut new_triangles: HashSet<(usize, usize, usize)> = HashSet::new();
-    for (a, b, c) in triangles {
-        if double_boundary_indices.contains(a)
-            || double_boundary_indices.contains(b)
-            || double_boundary_indices.contains(c)
-        {
-            continue;
-        }
-        new_triangles.insert((*a, *b, *c));
-    }
-
-    new_triangles
+        .collect()
 }
 
 fn dfs(node: usize, adj: &HashMap<usize, HashSet<usize>>, visited: &mut HashMap<usize, bool>) {
@@ -208,7 +676,7 @@ fn dfs(node: usize, adj: &HashMap<usize, HashSet<usize>>, visited: &mut HashMap<
         if !visited[&vertex] {
             visited.insert(vertex, true);
             for neighbor in &adj[&vertex] {
-                if !visited[&neighbor] {
+                if !visited[neighbor] {
                     stack.push(*neighbor);
                 }
             }
@@ -240,9 +708,9 @@ fn number_of_components(edges: &HashSet<(usize, usize)>) -> usize {
     component_count
 }
 
-fn remove_hourglass_indices(
-    triangles: &HashSet<(usize, usize, usize)>,
-) -> HashSet<(usize, usize, usize)> {
+/// Vertices whose triangles form more than one fan, e.g. the tip where two
+/// cones touch.
+fn find_hourglass_indices(triangles: &[Triplet]) -> HashSet<usize> {
     let mut indices: HashSet<usize> = HashSet::new();
     for (a, b, c) in triangles {
         indices.insert(*a);
@@ -274,13 +742,113 @@ fn remove_hourglass_indices(
         }
     }
 
-    let mut new_triangles: HashSet<(usize, usize, usize)> = HashSet::new();
-    for (a, b, c) in triangles {
-        if bad_indices.contains(a) || bad_indices.contains(b) || bad_indices.contains(c) {
-            continue;
+    bad_indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orient_triangles_keeps_order() {
+        // the second and third triangle are wound the opposite way
+        let triangles = vec![(0, 1, 2), (1, 2, 3), (3, 2, 4)];
+        assert_eq!(
+            orient_triangles(&triangles, false),
+            vec![Some((0, 1, 2)), Some((3, 2, 1)), Some((4, 2, 3))]
+        );
+        assert_eq!(
+            orient_triangles(&triangles, true),
+            vec![Some((0, 1, 2)), None, Some((3, 2, 4))]
+        );
+    }
+
+    #[test]
+    fn test_orient_every_component() {
+        // two separate pairs of triangles, each with one flipped triangle
+        let triangles = vec![(0, 1, 2), (1, 2, 3), (4, 5, 6), (5, 6, 7)];
+        assert_eq!(
+            orient_triangles(&triangles, false),
+            vec![
+                Some((0, 1, 2)),
+                Some((3, 2, 1)),
+                Some((4, 5, 6)),
+                Some((7, 6, 5))
+            ]
+        );
+        assert_eq!(
+            orient_triangles(&triangles, true),
+            vec![Some((0, 1, 2)), None, Some((4, 5, 6)), None]
+        );
+    }
+
+    #[test]
+    fn test_make_manifold() {
+        // two tetrahedra touching in vertex 0
+        let triangles = [
+            (0, 2, 1),
+            (0, 1, 3),
+            (1, 2, 3),
+            (0, 3, 2),
+            (0, 4, 5),
+            (0, 5, 6),
+            (0, 6, 4),
+            (4, 6, 5),
+        ];
+        let kept = triangles.iter().copied().enumerate().collect();
+        let mut dropped = vec![None; triangles.len()];
+
+        let (kept, iterations) = make_manifold(kept, &mut dropped, 10);
+        assert_eq!(kept, vec![(2, (1, 2, 3)), (7, (4, 6, 5))]);
+        assert_eq!(iterations, vec![[0, 0, 6], [0, 0, 0]]);
+        assert_eq!(dropped[0], Some(DropReason::Hourglass(0)));
+
+        let (_, iterations) = make_manifold(kept, &mut dropped, 1);
+        assert_eq!(iterations, vec![[0, 0, 0]]);
+    }
+
+    #[test]
+    fn test_make_manifold_repeats() {
+        // a 6 x 6 grid of points with a notch at the top, where the triangle
+        // from 1 to 2 to 8 is missing, and a tetrahedron touching it in 14
+        let mut triangles = Vec::new();
+        for row in 0..5 {
+            for column in 0..5 {
+                let a = 6 * row + column;
+                triangles.push((a, a + 1, a + 7));
+                triangles.push((a, a + 7, a + 6));
+            }
         }
-        new_triangles.insert((*a, *b, *c));
+        triangles.retain(|&t| t != (1, 2, 8));
+        triangles.extend([(14, 36, 37), (14, 37, 38), (14, 38, 36), (36, 38, 37)]);
+        let kept = triangles.iter().copied().enumerate().collect();
+        let mut dropped = vec![None; triangles.len()];
+
+        // removing the triangles around 14 leaves a hole which touches the
+        // notch in 8, which only the second iteration can remove
+        let (_, iterations) = make_manifold(kept, &mut dropped, 10);
+        assert_eq!(iterations, vec![[0, 0, 9], [0, 3, 0], [0, 0, 0]]);
+        let notch = triangles.iter().position(|&t| t == (1, 8, 7)).unwrap();
+        assert_eq!(dropped[notch], Some(DropReason::DoubleBoundary(8)));
     }
 
-    new_triangles
+    #[test]
+    fn test_summary() {
+        let dropped = vec![
+            None,
+            Some(DropReason::Duplicate),
+            Some(DropReason::Hourglass(7)),
+            Some(DropReason::Hourglass(7)),
+        ];
+        assert_eq!(
+            summary(&dropped),
+            "stage 1 (duplicate): removed 1\n\
+             stage 2 (orientation conflict): removed 0\n\
+             stage 3 (double boundary vertex): removed 0\n\
+             stage 4 (hourglass vertex): removed 2\n\
+             stage 5 (small component): removed 0\n\
+             stage 6 (non-manifold edge): removed 0\n\
+             kept 1 of 4 triangles\n"
+        );
+    }
 }
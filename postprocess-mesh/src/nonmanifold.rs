@@ -0,0 +1,382 @@
+use clap::ValueEnum;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use mesh::vector::{vec_cross_vec, vec_dot_vec, vec_minus_vec, Vector3};
+use mesh::Triplet;
+
+use crate::ordered;
+
+/// How to deal with edges shared by more than two triangles.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Repair {
+    /// Leave them to the orientation stage, which drops the triangles it
+    /// cannot orient
+    Keep,
+    /// Give each additional sheet meeting at the edge its own copies of the
+    /// edge's vertices
+    Split,
+    /// Keep the pair of triangles which fits best and remove the others
+    Remove,
+}
+
+/// Triangles sharing each edge, by their position.
+fn edge_to_triangles(triangles: &[Triplet]) -> HashMap<(usize, usize), Vec<usize>> {
+    let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (i, &(a, b, c)) in triangles.iter().enumerate() {
+        for (u, v) in [(a, b), (b, c), (c, a)] {
+            edges.entry(ordered(u, v)).or_default().push(i);
+        }
+    }
+    edges
+}
+
+/// Edges with more than two triangles, in ascending order.
+fn non_manifold_edges(
+    edges: &HashMap<(usize, usize), Vec<usize>>,
+) -> Vec<((usize, usize), Vec<usize>)> {
+    let mut result: Vec<_> = edges
+        .iter()
+        .filter(|(_, faces)| faces.len() > 2)
+        .map(|(&edge, faces)| (edge, faces.clone()))
+        .collect();
+    result.sort();
+    result
+}
+
+/// Triangles around each vertex, by their position.
+fn vertex_to_triangles(triangles: &[Triplet]) -> HashMap<usize, Vec<usize>> {
+    let mut vertices: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, &(a, b, c)) in triangles.iter().enumerate() {
+        for v in [a, b, c] {
+            vertices.entry(v).or_default().push(i);
+        }
+    }
+    vertices
+}
+
+/// The fan of each triangle around `vertex`, named after its first triangle.
+/// Triangles are in the same fan if they are connected through edges ending
+/// in `vertex` which have exactly two triangles.
+fn fans(
+    triangles: &[Triplet],
+    edges: &HashMap<(usize, usize), Vec<usize>>,
+    around: &[usize],
+    vertex: usize,
+) -> HashMap<usize, usize> {
+    let mut fan = HashMap::new();
+    for &start in around {
+        if fan.contains_key(&start) {
+            continue;
+        }
+        let mut stack = vec![start];
+        fan.insert(start, start);
+        while let Some(i) = stack.pop() {
+            let (a, b, c) = triangles[i];
+            for w in [a, b, c].into_iter().filter(|&w| w != vertex) {
+                if let [j, k] = edges[&ordered(vertex, w)][..] {
+                    let other = if j == i { k } else { j };
+                    if let Entry::Vacant(entry) = fan.entry(other) {
+                        entry.insert(start);
+                        stack.push(other);
+                    }
+                }
+            }
+        }
+    }
+    fan
+}
+
+/// Splits the triangles at an edge into sheets of one or two triangles,
+/// best fitting pair first. Triangles which already belong to the same fan
+/// around one of the edge's vertices, e.g. two sides of a closed shell, fit
+/// best. Otherwise a pair fits well if it traverses the edge in opposite
+/// directions, has normals pointing along the same line and, after that, a
+/// large area.
+fn sheets(
+    coordinates: &[Vector3],
+    triangles: &[Triplet],
+    edges: &HashMap<(usize, usize), Vec<usize>>,
+    vertices: &HashMap<usize, Vec<usize>>,
+    edge: (usize, usize),
+    faces: &[usize],
+) -> Vec<Vec<usize>> {
+    let normal = |i: usize| {
+        let (a, b, c) = triangles[i];
+        let (a, b, c) = (&coordinates[a], &coordinates[b], &coordinates[c]);
+        vec_cross_vec(&vec_minus_vec(b, a), &vec_minus_vec(c, a))
+    };
+    let length = |n: &Vector3| vec_dot_vec(n, n).sqrt();
+    let forward = |i: usize| {
+        let (a, b, c) = triangles[i];
+        [(a, b), (b, c), (c, a)].contains(&edge)
+    };
+    let first_fans = fans(triangles, edges, &vertices[&edge.0], edge.0);
+    let second_fans = fans(triangles, edges, &vertices[&edge.1], edge.1);
+
+    let mut pairs = Vec::new();
+    for (k, &i) in faces.iter().enumerate() {
+        for &j in &faces[k + 1..] {
+            let same_fan = first_fans[&i] == first_fans[&j] || second_fans[&i] == second_fans[&j];
+            let (ni, nj) = (normal(i), normal(j));
+            let lengths = length(&ni) * length(&nj);
+            let agreement = if lengths > 0.0 {
+                (vec_dot_vec(&ni, &nj) / lengths).abs()
+            } else {
+                0.0
+            };
+            let opposite = forward(i) != forward(j);
+            let area = length(&ni) + length(&nj);
+            pairs.push((same_fan, opposite, agreement, area, i, j));
+        }
+    }
+    pairs.sort_by(|p, q| {
+        (q.0, q.1, q.2, q.3)
+            .partial_cmp(&(p.0, p.1, p.2, p.3))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut used = HashSet::new();
+    let mut result = Vec::new();
+    for (_, _, _, _, i, j) in pairs {
+        if !used.contains(&i) && !used.contains(&j) {
+            used.insert(i);
+            used.insert(j);
+            result.push(vec![i, j]);
+        }
+    }
+    for &i in faces {
+        if !used.contains(&i) {
+            result.push(vec![i]);
+        }
+    }
+    result
+}
+
+/// Positions of the triangles to remove so that no edge has more than two
+/// triangles: at each such edge all but the best fitting pair.
+pub fn find_extra_triangles(coordinates: &[Vector3], triangles: &[Triplet]) -> HashSet<usize> {
+    let edges = edge_to_triangles(triangles);
+    let vertices = vertex_to_triangles(triangles);
+    let mut removed = HashSet::new();
+    for (edge, faces) in non_manifold_edges(&edges) {
+        let faces: Vec<usize> = faces.into_iter().filter(|f| !removed.contains(f)).collect();
+        if faces.len() > 2 {
+            for sheet in sheets(coordinates, triangles, &edges, &vertices, edge, &faces)
+                .iter()
+                .skip(1)
+            {
+                removed.extend(sheet);
+            }
+        }
+    }
+    removed
+}
+
+/// Separates the sheets meeting at edges with more than two triangles by
+/// giving every sheet but the best fitting one new copies of the vertices
+/// of the edge. Returns the new triangles, for each new vertex numbered from
+/// `coordinates.len()` on the vertex it copies, and the edges, by their input
+/// vertices, which still have more than two triangles.
+///
+/// A vertex is only split if the sheets are not connected through other
+/// triangles around it.
+pub fn split_sheets(
+    coordinates: &[Vector3],
+    triangles: &[Triplet],
+) -> (Vec<Triplet>, Vec<usize>, Vec<(usize, usize)>) {
+    let edges = edge_to_triangles(triangles);
+    let vertices = vertex_to_triangles(triangles);
+
+    // triangles connected across each edge, only within a sheet at
+    // non-manifold edges
+    let mut neighbors: Vec<Vec<(usize, (usize, usize))>> = vec![Vec::new(); triangles.len()];
+    let mut split_vertices = Vec::new();
+    for (&edge, faces) in &edges {
+        let sheets = if faces.len() > 2 {
+            split_vertices.extend([edge.0, edge.1]);
+            sheets(coordinates, triangles, &edges, &vertices, edge, faces)
+        } else {
+            vec![faces.clone()]
+        };
+        for sheet in sheets {
+            if let [i, j] = sheet[..] {
+                neighbors[i].push((j, edge));
+                neighbors[j].push((i, edge));
+            }
+        }
+    }
+    split_vertices.sort_unstable();
+    split_vertices.dedup();
+
+    let mut new_triangles = triangles.to_vec();
+    let mut copies = Vec::new();
+    for vertex in split_vertices {
+        // group the triangles around the vertex which are connected through
+        // edges ending in it, the first group keeps the vertex
+        let mut group: HashMap<usize, usize> = HashMap::new();
+        let mut num_groups = 0;
+        for &start in &vertices[&vertex] {
+            if group.contains_key(&start) {
+                continue;
+            }
+            let new_vertex = if num_groups == 0 {
+                vertex
+            } else {
+                copies.push(vertex);
+                coordinates.len() + copies.len() - 1
+            };
+            num_groups += 1;
+
+            let mut stack = vec![start];
+            group.insert(start, new_vertex);
+            while let Some(i) = stack.pop() {
+                for &(j, (u, v)) in &neighbors[i] {
+                    if (u == vertex || v == vertex) && !group.contains_key(&j) {
+                        group.insert(j, new_vertex);
+                        stack.push(j);
+                    }
+                }
+            }
+        }
+
+        for (i, new_vertex) in group {
+            let (a, b, c) = &mut new_triangles[i];
+            for corner in [a, b, c] {
+                if *corner == vertex {
+                    *corner = new_vertex;
+                }
+            }
+        }
+    }
+
+    let source = |v: usize| {
+        if v < coordinates.len() {
+            v
+        } else {
+            copies[v - coordinates.len()]
+        }
+    };
+    let mut unresolved: Vec<(usize, usize)> =
+        non_manifold_edges(&edge_to_triangles(&new_triangles))
+            .into_iter()
+            .map(|((u, v), _)| ordered(source(u), source(v)))
+            .collect();
+    unresolved.sort_unstable();
+    unresolved.dedup();
+
+    (new_triangles, copies, unresolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two squares in the planes z = 0 and x = 0 crossing along the edge
+    /// from 0 to 1, plus a flap on that edge.
+    fn crossing() -> (Vec<Vector3>, Vec<Triplet>) {
+        let point = |x, y, z| Vector3 { x, y, z };
+        let coordinates = vec![
+            point(0.0, 0.0, 0.0),
+            point(0.0, 1.0, 0.0),
+            point(1.0, 0.5, 0.0),
+            point(-1.0, 0.5, 0.0),
+            point(0.0, 0.5, 1.0),
+            point(0.0, 0.5, -1.0),
+            point(0.3, 0.5, 0.1),
+        ];
+        let triangles = vec![(0, 1, 2), (1, 0, 3), (0, 1, 4), (1, 0, 5), (1, 0, 6)];
+        (coordinates, triangles)
+    }
+
+    #[test]
+    fn test_remove() {
+        let (coordinates, triangles) = crossing();
+        let removed = find_extra_triangles(&coordinates, &triangles[..4]);
+        assert_eq!(removed.len(), 2);
+
+        // the flap is almost in the plane of the square that is kept, but
+        // cannot pair with it as well as the square's own two triangles
+        let removed = find_extra_triangles(&coordinates, &triangles);
+        assert_eq!(removed, HashSet::from([2, 3, 4]));
+    }
+
+    #[test]
+    fn test_split() {
+        let (coordinates, triangles) = crossing();
+        let (triangles, copies, unresolved) = split_sheets(&coordinates, &triangles[..4]);
+
+        assert_eq!(copies, vec![0, 1]);
+        assert_eq!(triangles, vec![(0, 1, 2), (1, 0, 3), (7, 8, 4), (8, 7, 5)]);
+        assert!(non_manifold_edges(&edge_to_triangles(&triangles)).is_empty());
+        assert!(unresolved.is_empty());
+    }
+
+    /// Two tetrahedra sharing the edge from 0 to 1. The first side of each
+    /// lies in the plane of the second side of the other, so the sides pair
+    /// up best across the two tetrahedra if only geometry is considered.
+    fn shells() -> (Vec<Vector3>, Vec<Triplet>) {
+        let point = |x, y, z| Vector3 { x, y, z };
+        let coordinates = vec![
+            point(0.0, 0.0, 0.0),
+            point(0.0, 0.0, 1.0),
+            point(1.0, 0.0, 0.5),
+            point(0.0, 1.0, 0.5),
+            point(0.0, -1.0, 0.5),
+            point(-1.0, 0.0, 0.5),
+        ];
+        let tetrahedron = |p, q| [(0, 1, p), (1, 0, q), (0, p, q), (p, 1, q)];
+        let triangles = tetrahedron(2, 3)
+            .into_iter()
+            .chain(tetrahedron(4, 5))
+            .collect();
+        (coordinates, triangles)
+    }
+
+    #[test]
+    fn test_split_shells() {
+        let (coordinates, triangles) = shells();
+        let (triangles, copies, unresolved) = split_sheets(&coordinates, &triangles);
+
+        assert_eq!(copies, vec![0, 1]);
+        assert_eq!(triangles[..4], [(0, 1, 2), (1, 0, 3), (0, 2, 3), (2, 1, 3)]);
+        assert_eq!(triangles[4..], [(6, 7, 4), (7, 6, 5), (6, 4, 5), (4, 7, 5)]);
+        assert!(unresolved.is_empty());
+
+        // each tetrahedron keeps both of its sides at the edge
+        let (coordinates, triangles) = shells();
+        let removed = find_extra_triangles(&coordinates, &triangles);
+        assert_eq!(removed, HashSet::from([4, 5]));
+    }
+
+    #[test]
+    fn test_unresolved() {
+        // the edges from 2 to 3, 0 to 3 and 2 to 5 of an octahedron each have
+        // three triangles, and the sheets at one edge are tied to those at
+        // the others through the triangles around their vertices
+        let point = |x, y, z| Vector3 { x, y, z };
+        let coordinates = vec![
+            point(1.0, 0.0, 0.0),
+            point(-1.0, 0.0, 0.0),
+            point(0.0, 1.0, 0.0),
+            point(0.0, -1.0, 0.0),
+            point(0.0, 0.0, 1.0),
+            point(0.0, 0.0, -1.0),
+        ];
+        let triangles = vec![
+            (2, 3, 4),
+            (3, 0, 1),
+            (0, 5, 2),
+            (2, 5, 1),
+            (0, 2, 3),
+            (3, 2, 5),
+            (0, 3, 5),
+        ];
+        let (triangles, _, unresolved) = split_sheets(&coordinates, &triangles);
+        assert_eq!(unresolved, vec![(2, 3)]);
+        assert_eq!(
+            non_manifold_edges(&edge_to_triangles(&triangles)),
+            vec![((2, 3), vec![0, 4, 5])]
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use mesh::vector::{vec_cross_vec, vec_dot_vec, vec_minus_vec, Vector3};
+use mesh::Triplet;
+
+/// Flips the consistently wound components whose normals point inward and
+/// returns how many were flipped. `components` gives the component of each
+/// triangle.
+///
+/// A component points outward if the flux of x - c through it is positive,
+/// where c is its area weighted centroid. For a closed shell this flux is
+/// three times the signed volume, independent of c. For an open shell, e.g.
+/// a scalp cut off at the neck, it measures whether the normals point away
+/// from the centroid.
+pub fn orient_outward(
+    coordinates: &[Vector3],
+    triangles: &mut [Triplet],
+    components: &[usize],
+) -> usize {
+    let num_components = components.iter().max().map_or(0, |m| m + 1);
+
+    // twice the area and the centroid of each triangle
+    let (normals, centroids): (Vec<Vector3>, Vec<Vector3>) = triangles
+        .iter()
+        .map(|&(a, b, c)| {
+            let (a, b, c) = (&coordinates[a], &coordinates[b], &coordinates[c]);
+            let normal = vec_cross_vec(&vec_minus_vec(b, a), &vec_minus_vec(c, a));
+            let centroid = Vector3 {
+                x: (a.x + b.x + c.x) / 3.0,
+                y: (a.y + b.y + c.y) / 3.0,
+                z: (a.z + b.z + c.z) / 3.0,
+            };
+            (normal, centroid)
+        })
+        .unzip();
+
+    let mut area = vec![0.0; num_components];
+    let mut weighted_sum = vec![[0.0; 3]; num_components];
+    for ((normal, centroid), &component) in normals.iter().zip(&centroids).zip(components) {
+        let a = vec_dot_vec(normal, normal).sqrt();
+        area[component] += a;
+        weighted_sum[component][0] += a * centroid.x;
+        weighted_sum[component][1] += a * centroid.y;
+        weighted_sum[component][2] += a * centroid.z;
+    }
+
+    let mut flux = vec![0.0; num_components];
+    for ((normal, centroid), &component) in normals.iter().zip(&centroids).zip(components) {
+        let [x, y, z] = weighted_sum[component].map(|s| s / area[component]);
+        let center = Vector3 { x, y, z };
+        flux[component] += vec_dot_vec(normal, &vec_minus_vec(centroid, &center));
+    }
+
+    for (triangle, &component) in triangles.iter_mut().zip(components) {
+        if flux[component] < 0.0 {
+            let (a, b, c) = *triangle;
+            *triangle = (c, b, a);
+        }
+    }
+
+    flux.iter().filter(|&&f| f < 0.0).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64, z: f64) -> Vector3 {
+        Vector3 { x, y, z }
+    }
+
+    #[test]
+    fn test_flip_inward_components() {
+        let mut coordinates = vec![
+            point(0.0, 0.0, 0.0),
+            point(1.0, 0.0, 0.0),
+            point(0.0, 1.0, 0.0),
+            point(0.0, 0.0, 1.0),
+        ];
+        // a second tetrahedron far away, together with the first
+        coordinates.extend(
+            coordinates
+                .clone()
+                .iter()
+                .map(|p| point(p.x + 10.0, p.y, p.z)),
+        );
+        let outward = [(0, 2, 1), (0, 1, 3), (1, 2, 3), (0, 3, 2)];
+        let inward = outward.map(|(a, b, c)| (a + 4, c + 4, b + 4));
+
+        let mut triangles: Vec<Triplet> = outward.into_iter().chain(inward).collect();
+        let components = [0, 0, 0, 0, 1, 1, 1, 1];
+        assert_eq!(orient_outward(&coordinates, &mut triangles, &components), 1);
+        assert_eq!(triangles[..4], outward);
+        assert_eq!(triangles[4], (6, 5, 4));
+    }
+
+    #[test]
+    fn test_open_shell() {
+        // a tetrahedron without its bottom, wound inward
+        let coordinates = vec![
+            point(0.0, 0.0, 0.0),
+            point(1.0, 0.0, 0.0),
+            point(0.0, 1.0, 0.0),
+            point(0.3, 0.3, 1.0),
+        ];
+        let mut triangles = vec![(0, 3, 1), (1, 3, 2), (0, 2, 3)];
+        assert_eq!(orient_outward(&coordinates, &mut triangles, &[0, 0, 0]), 1);
+        assert_eq!(triangles, vec![(1, 3, 0), (2, 3, 1), (3, 2, 0)]);
+    }
+}
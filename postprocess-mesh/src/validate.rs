@@ -0,0 +1,395 @@
+use clap::ValueEnum;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use mesh::vector::{vec_cross_vec, vec_minus_vec};
+use mesh::{Mesh, Triplet};
+
+use crate::{find_hourglass_indices, number_of_components, ordered};
+
+/// Checks which can make validation fail, given on the command line with
+/// `--fail-on`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Check {
+    /// Triangles refer to vertices which do not exist
+    OutOfRangeIndices,
+    /// Triangles with a repeated vertex or zero area
+    DegenerateTriangles,
+    /// Triangles with the same vertices as an earlier one
+    DuplicateTriangles,
+    /// Vertices with the same coordinates as an earlier one
+    DuplicateVertices,
+    /// Edges shared by more than two triangles
+    NonManifoldEdges,
+    /// Vertices whose triangles form more than one fan
+    NonManifoldVertices,
+    /// The surface has holes
+    BoundaryLoops,
+    /// The surface falls apart into more than one piece
+    MultipleComponents,
+    /// Neighboring triangles are wound in opposite directions
+    InconsistentOrientation,
+}
+
+impl Check {
+    pub fn name(&self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+/// What the checks found. Only triangles with valid indices are used for
+/// everything but `out_of_range_indices`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub num_vertices: usize,
+    pub num_triangles: usize,
+    pub out_of_range_indices: usize,
+    pub degenerate_triangles: usize,
+    pub duplicate_triangles: usize,
+    pub duplicate_vertices: usize,
+    pub non_manifold_edges: usize,
+    pub non_manifold_vertices: usize,
+    pub boundary_edges: usize,
+    pub boundary_loops: usize,
+    pub components: usize,
+    pub euler_characteristic: i64,
+    /// Only defined for consistently oriented manifolds.
+    pub genus: Option<i64>,
+    /// Edges traversed in the same direction by both of their triangles.
+    pub inconsistent_edges: usize,
+}
+
+impl Report {
+    pub fn new(mesh: &Mesh) -> Report {
+        let n = mesh.vertices.len();
+        let out_of_range_indices = mesh
+            .triangles
+            .iter()
+            .flat_map(|&(a, b, c)| [a, b, c])
+            .filter(|&i| i >= n)
+            .count();
+        let triangles: Vec<Triplet> = mesh
+            .triangles
+            .iter()
+            .filter(|&&(a, b, c)| a < n && b < n && c < n)
+            .copied()
+            .collect();
+
+        let degenerate_triangles = triangles
+            .iter()
+            .filter(|&&(a, b, c)| {
+                let v = &mesh.vertices;
+                let normal =
+                    vec_cross_vec(&vec_minus_vec(&v[b], &v[a]), &vec_minus_vec(&v[c], &v[a]));
+                a == b || b == c || c == a || normal.x == 0.0 && normal.y == 0.0 && normal.z == 0.0
+            })
+            .count();
+
+        let mut seen = HashSet::new();
+        let duplicate_triangles = triangles
+            .iter()
+            .filter(|&&(a, b, c)| {
+                let mut sorted = [a, b, c];
+                sorted.sort();
+                !seen.insert(sorted)
+            })
+            .count();
+
+        let mut seen = HashSet::new();
+        let duplicate_vertices = mesh
+            .vertices
+            .iter()
+            .filter(|p| !seen.insert([p.x, p.y, p.z].map(|x| (x + 0.0).to_bits())))
+            .count();
+
+        let mut edge_count: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut half_edge_count: HashMap<(usize, usize), usize> = HashMap::new();
+        for &(a, b, c) in &triangles {
+            for (u, v) in [(a, b), (b, c), (c, a)] {
+                *edge_count.entry(ordered(u, v)).or_insert(0) += 1;
+                *half_edge_count.entry((u, v)).or_insert(0) += 1;
+            }
+        }
+        let non_manifold_edges = edge_count.values().filter(|&&k| k > 2).count();
+        let boundary: HashSet<(usize, usize)> = edge_count
+            .iter()
+            .filter(|(_, &k)| k == 1)
+            .map(|(&e, _)| e)
+            .collect();
+        let inconsistent_edges = edge_count
+            .iter()
+            .filter(|(&(u, v), &k)| {
+                k == 2
+                    && (half_edge_count.contains_key(&(u, v))
+                        != half_edge_count.contains_key(&(v, u)))
+            })
+            .count();
+
+        let non_manifold_vertices = find_hourglass_indices(&triangles).len();
+        let referenced: HashSet<usize> =
+            triangles.iter().flat_map(|&(a, b, c)| [a, b, c]).collect();
+        let edges: HashSet<(usize, usize)> = edge_count.keys().copied().collect();
+        let components = number_of_components(&edges);
+        let boundary_loops = count_boundary_loops(&triangles, &boundary);
+
+        let euler_characteristic =
+            referenced.len() as i64 - edge_count.len() as i64 + triangles.len() as i64;
+        // chi = 2 c - 2 g - b for orientable surfaces with c components and b holes
+        let twice_genus = 2 * components as i64 - boundary_loops as i64 - euler_characteristic;
+        let manifold = non_manifold_edges == 0 && non_manifold_vertices == 0;
+        let genus =
+            (manifold && inconsistent_edges == 0 && twice_genus >= 0 && twice_genus % 2 == 0)
+                .then_some(twice_genus / 2);
+
+        Report {
+            num_vertices: n,
+            num_triangles: mesh.triangles.len(),
+            out_of_range_indices,
+            degenerate_triangles,
+            duplicate_triangles,
+            duplicate_vertices,
+            non_manifold_edges,
+            non_manifold_vertices,
+            boundary_edges: boundary.len(),
+            boundary_loops,
+            components,
+            euler_characteristic,
+            genus,
+            inconsistent_edges,
+        }
+    }
+
+    /// The checks among `checks` which fail for this mesh.
+    pub fn failed(&self, checks: &[Check]) -> Vec<Check> {
+        checks
+            .iter()
+            .filter(|check| match check {
+                Check::OutOfRangeIndices => self.out_of_range_indices > 0,
+                Check::DegenerateTriangles => self.degenerate_triangles > 0,
+                Check::DuplicateTriangles => self.duplicate_triangles > 0,
+                Check::DuplicateVertices => self.duplicate_vertices > 0,
+                Check::NonManifoldEdges => self.non_manifold_edges > 0,
+                Check::NonManifoldVertices => self.non_manifold_vertices > 0,
+                Check::BoundaryLoops => self.boundary_loops > 0,
+                Check::MultipleComponents => self.components > 1,
+                Check::InconsistentOrientation => self.inconsistent_edges > 0,
+            })
+            .copied()
+            .collect()
+    }
+
+    fn entries(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("vertices", self.num_vertices.to_string()),
+            ("triangles", self.num_triangles.to_string()),
+            (
+                "out_of_range_indices",
+                self.out_of_range_indices.to_string(),
+            ),
+            (
+                "degenerate_triangles",
+                self.degenerate_triangles.to_string(),
+            ),
+            ("duplicate_triangles", self.duplicate_triangles.to_string()),
+            ("duplicate_vertices", self.duplicate_vertices.to_string()),
+            ("non_manifold_edges", self.non_manifold_edges.to_string()),
+            (
+                "non_manifold_vertices",
+                self.non_manifold_vertices.to_string(),
+            ),
+            ("boundary_edges", self.boundary_edges.to_string()),
+            ("boundary_loops", self.boundary_loops.to_string()),
+            ("components", self.components.to_string()),
+            (
+                "euler_characteristic",
+                self.euler_characteristic.to_string(),
+            ),
+            (
+                "genus",
+                self.genus.map_or("null".to_string(), |g| g.to_string()),
+            ),
+            ("inconsistent_edges", self.inconsistent_edges.to_string()),
+        ]
+    }
+
+    pub fn format(&self, format: ReportFormat, failed: &[Check]) -> String {
+        let mut s = String::new();
+        match format {
+            ReportFormat::Text => {
+                for (name, value) in self.entries() {
+                    writeln!(s, "{:<24}{}", name.replace('_', " "), value).unwrap();
+                }
+                let failed: Vec<String> = failed.iter().map(|c| c.name()).collect();
+                if failed.is_empty() {
+                    writeln!(s, "all checks passed").unwrap();
+                } else {
+                    writeln!(s, "failed checks: {}", failed.join(", ")).unwrap();
+                }
+            }
+            ReportFormat::Json => {
+                writeln!(s, "{{").unwrap();
+                for (name, value) in self.entries() {
+                    writeln!(s, "  \"{}\": {},", name, value).unwrap();
+                }
+                let failed: Vec<String> =
+                    failed.iter().map(|c| format!("\"{}\"", c.name())).collect();
+                writeln!(s, "  \"failed_checks\": [{}]", failed.join(", ")).unwrap();
+                writeln!(s, "}}").unwrap();
+            }
+        }
+        s
+    }
+}
+
+/// Number of boundary loops, following each boundary edge in the direction
+/// of its triangle. Where several loops meet in a vertex, an edge continues
+/// with an edge from another fan of triangles around the vertex, so that
+/// holes touching in a vertex count separately.
+fn count_boundary_loops(triangles: &[Triplet], boundary: &HashSet<(usize, usize)>) -> usize {
+    let mut edge_to_triangles: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (i, &(a, b, c)) in triangles.iter().enumerate() {
+        for (u, v) in [(a, b), (b, c), (c, a)] {
+            edge_to_triangles.entry(ordered(u, v)).or_default().push(i);
+        }
+    }
+
+    // the boundary edge at the other end of the fan around `vertex` which
+    // starts at the boundary edge from `other` to `vertex`
+    let fan_end = |mut other: usize, vertex: usize| {
+        let mut triangle = edge_to_triangles[&ordered(other, vertex)][0];
+        for _ in 0..triangles.len() {
+            let (a, b, c) = triangles[triangle];
+            let third = [a, b, c].into_iter().find(|&w| w != vertex && w != other)?;
+            let edge = ordered(vertex, third);
+            match edge_to_triangles[&edge][..] {
+                [_] => return Some(edge),
+                [i, j] => triangle = if i == triangle { j } else { i },
+                _ => return None,
+            }
+            other = third;
+        }
+        None
+    };
+
+    // boundary edges ending and starting in each vertex
+    let mut incoming: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut outgoing: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+    for &(a, b, c) in triangles {
+        for (u, v) in [(a, b), (b, c), (c, a)] {
+            if boundary.contains(&ordered(u, v)) {
+                incoming.entry(v).or_default().push(u);
+                outgoing.entry(u).or_default().push(ordered(u, v));
+            }
+        }
+    }
+
+    let edges: Vec<(usize, usize)> = boundary.iter().copied().collect();
+    let position: HashMap<(usize, usize), usize> =
+        edges.iter().enumerate().map(|(i, &e)| (e, i)).collect();
+    let mut links: HashSet<(usize, usize)> = (0..edges.len()).map(|i| (i, i)).collect();
+    for (vertex, sources) in incoming {
+        let mut unused = outgoing.get(&vertex).cloned().unwrap_or_default();
+        for source in sources {
+            let same_fan = fan_end(source, vertex);
+            let choice = unused
+                .iter()
+                .position(|&e| unused.len() == 1 || Some(e) != same_fan)
+                .or((!unused.is_empty()).then_some(0));
+            if let Some(k) = choice {
+                let next = unused.swap_remove(k);
+                links.insert(ordered(position[&ordered(source, vertex)], position[&next]));
+            }
+        }
+    }
+    number_of_components(&links)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mesh::vector::Vector3;
+
+    fn tetrahedron() -> Mesh {
+        let point = |x, y, z| Vector3 { x, y, z };
+        Mesh::new(
+            vec![
+                point(0.0, 0.0, 0.0),
+                point(1.0, 0.0, 0.0),
+                point(0.0, 1.0, 0.0),
+                point(0.0, 0.0, 1.0),
+            ],
+            vec![(0, 2, 1), (0, 1, 3), (1, 2, 3), (0, 3, 2)],
+        )
+    }
+
+    #[test]
+    fn test_closed_surface() {
+        let report = Report::new(&tetrahedron());
+        assert_eq!(report.euler_characteristic, 2);
+        assert_eq!(report.genus, Some(0));
+        assert_eq!(report.components, 1);
+        assert_eq!(report.boundary_loops, 0);
+        assert!(report.failed(Check::value_variants()).is_empty());
+    }
+
+    #[test]
+    fn test_problems() {
+        let mut mesh = tetrahedron();
+        mesh.triangles[3] = (0, 2, 3);
+        mesh.triangles.push((1, 0, 2));
+        mesh.triangles.push((0, 1, 7));
+        let report = Report::new(&mesh);
+
+        assert_eq!(report.out_of_range_indices, 1);
+        assert_eq!(report.duplicate_triangles, 1);
+        assert_eq!(report.non_manifold_edges, 3);
+        assert_eq!(report.genus, None);
+        assert_eq!(
+            report.failed(&[
+                Check::OutOfRangeIndices,
+                Check::DegenerateTriangles,
+                Check::InconsistentOrientation
+            ]),
+            vec![Check::OutOfRangeIndices, Check::InconsistentOrientation]
+        );
+
+        let json = report.format(ReportFormat::Json, &[Check::OutOfRangeIndices]);
+        assert!(json.contains("  \"genus\": null,\n"));
+        assert!(json.ends_with("  \"failed_checks\": [\"out-of-range-indices\"]\n}\n"));
+    }
+
+    #[test]
+    fn test_holes_touching_in_a_vertex() {
+        // a 5 x 5 grid of points without two triangles which only share the
+        // point 12 in the middle
+        let mut vertices = Vec::new();
+        for row in 0..5 {
+            for column in 0..5 {
+                vertices.push(Vector3 {
+                    x: column as f64,
+                    y: row as f64,
+                    z: 0.0,
+                });
+            }
+        }
+        let mut triangles = Vec::new();
+        for row in 0..4 {
+            for column in 0..4 {
+                let a = 5 * row + column;
+                triangles.push((a, a + 1, a + 6));
+                triangles.push((a, a + 6, a + 5));
+            }
+        }
+        triangles.retain(|&t| t != (6, 7, 12) && t != (12, 18, 17));
+
+        let report = Report::new(&Mesh::new(vertices, triangles));
+        assert_eq!(report.boundary_edges, 22);
+        assert_eq!(report.boundary_loops, 3);
+    }
+}